@@ -0,0 +1,441 @@
+use crate::error::BotError;
+use crate::market_maker::{MarketMakerConfig, QuotingModel};
+use crate::market_state::{InventoryLimit, MarketState};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+
+/// Every problem found while loading a market config, reported together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub path: String,
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config {}:", self.path)?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A loaded config: the markets and the maker config each one quotes with
+#[derive(Debug, Clone)]
+pub struct MarketConfig {
+    pub markets: HashMap<String, MarketState>,
+    pub makers: HashMap<String, MarketMakerConfig>,
+}
+
+/// Load market definitions from a JSON file of the form
+/// `{ "markets": [{ "name": ..., "initial_mid": ..., ... }] }`.
+/// `inventory_limit`, `exposure_limit`, `spread`, `fee`, `capital`,
+/// `vol_scale`, `session_loss_cap`, `session_profit_cap`,
+/// `max_drawdown_limit`, `resolution_step`, `reversion_rate`,
+/// `reversion_anchor`, `drift`, `capital_events` (`[[step, amount], ...]`) and
+/// `inventory_limit_mode` (`"fixed"` or `{"capital_fraction": f}`) are
+/// optional, as are `accrue_rebates` (a bool) and `beta`.
+pub fn load_markets(path: &str) -> Result<HashMap<String, MarketState>, BotError> {
+    load_config(path).map(|config| config.markets)
+}
+
+/// Like `load_markets`, also reading each market's maker settings:
+/// `price_impact`, `tick_size`, `ladder_levels` and `quoting_model`
+/// (`"heuristic"` or `{"avellaneda_stoikov": {"gamma": .., "sigma": .., "k": ..}}`),
+/// all optional, over `MarketMakerConfig::default()`
+pub fn load_config(path: &str) -> Result<MarketConfig, BotError> {
+    let fail = |problem: String| {
+        BotError::Config(ConfigError {
+            path: path.to_string(),
+            problems: vec![problem],
+        })
+    };
+
+    let raw = fs::read_to_string(path).map_err(|e| fail(format!("cannot read file: {}", e)))?;
+    let root: Value = serde_json::from_str(&raw).map_err(|e| fail(format!("malformed JSON: {}", e)))?;
+    let entries = root
+        .get("markets")
+        .and_then(Value::as_array)
+        .ok_or_else(|| fail("expected a top-level `markets` array".to_string()))?;
+
+    let mut markets = HashMap::new();
+    let mut makers = HashMap::new();
+    let mut names = HashSet::new();
+    let mut problems = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if let Some((state, maker)) = parse_market(index, entry, &mut names, &mut problems) {
+            makers.insert(state.name.clone(), maker);
+            markets.insert(state.name.clone(), state);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(MarketConfig { markets, makers })
+    } else {
+        Err(BotError::Config(ConfigError {
+            path: path.to_string(),
+            problems,
+        }))
+    }
+}
+
+/// Parse one market entry, pushing a message for every bad field
+fn parse_market(
+    index: usize,
+    entry: &Value,
+    names: &mut HashSet<String>,
+    problems: &mut Vec<String>,
+) -> Option<(MarketState, MarketMakerConfig)> {
+    let before = problems.len();
+
+    let name = match entry.get("name").and_then(Value::as_str) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => {
+            problems.push(format!("markets[{}]: `name` must be a non-empty string", index));
+            format!("#{}", index)
+        }
+    };
+    if !names.insert(name.clone()) {
+        problems.push(format!("markets[{}] ({}): duplicate market name", index, name));
+    }
+
+    let mut field = |key: &str, default: Option<f64>, valid: fn(f64) -> bool, rule: &str| -> f64 {
+        match entry.get(key) {
+            None => match default {
+                Some(default) => default,
+                None => {
+                    problems.push(format!("markets[{}] ({}): missing `{}`", index, name, key));
+                    f64::NAN
+                }
+            },
+            Some(value) => match value.as_f64() {
+                Some(v) if v.is_finite() && valid(v) => v,
+                Some(v) => {
+                    problems.push(format!("markets[{}] ({}): `{}` = {} {}", index, name, key, v, rule));
+                    f64::NAN
+                }
+                None => {
+                    problems.push(format!("markets[{}] ({}): `{}` must be a number", index, name, key));
+                    f64::NAN
+                }
+            },
+        }
+    };
+
+    let initial_mid = field("initial_mid", None, |v| v > 0.0 && v < 1.0, "must be in (0, 1)");
+    let inventory_limit = field("inventory_limit", Some(100.0), |v| v >= 0.0, "must be >= 0");
+    let exposure_limit = field("exposure_limit", Some(10000.0), |v| v >= 0.0, "must be >= 0");
+    let spread = field("spread", Some(0.05), |v| v > 0.0 && v <= 1.0, "must be in (0, 1]");
+    let fee = field("fee", Some(0.0), |v| v.abs() < 1.0, "must be in (-1, 1)");
+
+    let capital = field("capital", Some(0.0), |v| v >= 0.0, "must be >= 0");
+    let vol_scale = field("vol_scale", Some(0.0), |v| v >= 0.0, "must be >= 0");
+    let reversion_rate = field("reversion_rate", Some(0.005), |v| (0.0..=1.0).contains(&v), "must be in [0, 1]");
+    let reversion_anchor = field("reversion_anchor", Some(0.5), |v| v > 0.0 && v < 1.0, "must be in (0, 1)");
+    let drift = field("drift", Some(0.0), |v| v.abs() < 1.0, "must be in (-1, 1)");
+    let beta = field("beta", Some(0.0), |_| true, "");
+    let price_impact = field("price_impact", Some(0.0), |v| v >= 0.0, "must be >= 0");
+    let tick_size = entry
+        .get("tick_size")
+        .map(|_| field("tick_size", None, |v| v > 0.0 && v < 1.0, "must be in (0, 1)"));
+    let mut optional = |key: &str| {
+        entry
+            .get(key)
+            .map(|_| field(key, None, |v| v > 0.0, "must be > 0"))
+    };
+    let session_loss_cap = optional("session_loss_cap");
+    let session_profit_cap = optional("session_profit_cap");
+    let max_drawdown_limit = optional("max_drawdown_limit");
+    let accrue_rebates = match entry.get("accrue_rebates") {
+        None => false,
+        Some(value) => value.as_bool().unwrap_or_else(|| {
+            problems.push(format!("markets[{}] ({}): `accrue_rebates` must be true or false", index, name));
+            false
+        }),
+    };
+    let capital_events = parse_capital_events(index, &name, entry, problems);
+    let inventory_limit_mode = parse_inventory_limit_mode(index, &name, entry, capital, problems);
+    let ladder_levels = match entry.get("ladder_levels") {
+        None => 1,
+        Some(value) => match value.as_u64().filter(|levels| *levels >= 1) {
+            Some(levels) => levels as usize,
+            None => {
+                problems.push(format!("markets[{}] ({}): `ladder_levels` must be an integer >= 1", index, name));
+                1
+            }
+        },
+    };
+    let quoting_model = parse_quoting_model(index, &name, entry, problems);
+    let resolution_step = match entry.get("resolution_step") {
+        None => None,
+        Some(value) => match value.as_u64() {
+            Some(step) => Some(step),
+            None => {
+                problems.push(format!(
+                    "markets[{}] ({}): `resolution_step` must be a non-negative integer",
+                    index, name
+                ));
+                None
+            }
+        },
+    };
+
+    if problems.len() > before {
+        return None;
+    }
+
+    let mut state = MarketState::new(&name, initial_mid);
+    state.inventory_limit = inventory_limit;
+    state.exposure_limit = exposure_limit;
+    state.spread = spread;
+    state.fee = fee;
+    state.accrue_rebates = accrue_rebates;
+    state.capital = capital;
+    state.vol_scale = vol_scale;
+    state.reversion_rate = reversion_rate;
+    state.reversion_anchor = reversion_anchor;
+    state.drift = drift;
+    state.beta = beta;
+    state.session_loss_cap = session_loss_cap;
+    state.session_profit_cap = session_profit_cap;
+    state.max_drawdown_limit = max_drawdown_limit;
+    state.capital_events = capital_events;
+    state.inventory_limit_mode = inventory_limit_mode;
+    state.resolution_step = resolution_step;
+    let maker = MarketMakerConfig {
+        price_impact,
+        tick_size,
+        ladder_levels,
+        quoting_model,
+        ..MarketMakerConfig::default()
+    };
+    Some((state, maker))
+}
+
+/// Optional `quoting_model`: `"heuristic"`, or `{"avellaneda_stoikov":
+/// {"gamma": g, "sigma": s, "k": k}}` with gamma and k > 0 and sigma >= 0
+fn parse_quoting_model(index: usize, name: &str, entry: &Value, problems: &mut Vec<String>) -> QuotingModel {
+    let Some(value) = entry.get("quoting_model") else {
+        return QuotingModel::Heuristic;
+    };
+    if value.as_str() == Some("heuristic") {
+        return QuotingModel::Heuristic;
+    }
+    let Some(params) = value.get("avellaneda_stoikov") else {
+        problems.push(format!(
+            "markets[{}] ({}): `quoting_model` must be \"heuristic\" or {{\"avellaneda_stoikov\": {{..}}}}",
+            index, name
+        ));
+        return QuotingModel::Heuristic;
+    };
+
+    let before = problems.len();
+    let mut param = |key: &str, valid: fn(f64) -> bool, rule: &str| match params.get(key).and_then(Value::as_f64) {
+        Some(v) if v.is_finite() && valid(v) => v,
+        _ => {
+            problems.push(format!(
+                "markets[{}] ({}): `quoting_model` avellaneda_stoikov `{}` must be a number {}",
+                index, name, key, rule
+            ));
+            f64::NAN
+        }
+    };
+    let gamma = param("gamma", |v| v > 0.0, "> 0");
+    let sigma = param("sigma", |v| v >= 0.0, ">= 0");
+    let k = param("k", |v| v > 0.0, "> 0");
+    if problems.len() > before {
+        return QuotingModel::Heuristic;
+    }
+    QuotingModel::AvellanedaStoikov { gamma, sigma, k }
+}
+
+/// Optional `inventory_limit_mode`: `"fixed"`, or `{"capital_fraction": f}`
+/// with f in (0, 1], which derives the limit from `capital`
+fn parse_inventory_limit_mode(
+    index: usize,
+    name: &str,
+    entry: &Value,
+    capital: f64,
+    problems: &mut Vec<String>,
+) -> InventoryLimit {
+    let Some(value) = entry.get("inventory_limit_mode") else {
+        return InventoryLimit::Fixed;
+    };
+    if value.as_str() == Some("fixed") {
+        return InventoryLimit::Fixed;
+    }
+    match value.get("capital_fraction").and_then(Value::as_f64) {
+        Some(fraction) if fraction > 0.0 && fraction <= 1.0 => {
+            if capital <= 0.0 {
+                problems.push(format!(
+                    "markets[{}] ({}): `inventory_limit_mode` capital_fraction needs `capital` > 0",
+                    index, name
+                ));
+            }
+            InventoryLimit::CapitalFraction { fraction }
+        }
+        Some(fraction) => {
+            problems.push(format!(
+                "markets[{}] ({}): `inventory_limit_mode` capital_fraction = {} must be in (0, 1]",
+                index, name, fraction
+            ));
+            InventoryLimit::Fixed
+        }
+        None => {
+            problems.push(format!(
+                "markets[{}] ({}): `inventory_limit_mode` must be \"fixed\" or {{\"capital_fraction\": f}}",
+                index, name
+            ));
+            InventoryLimit::Fixed
+        }
+    }
+}
+
+/// Optional `capital_events: [[step, amount], ...]`
+fn parse_capital_events(index: usize, name: &str, entry: &Value, problems: &mut Vec<String>) -> Vec<(u64, f64)> {
+    let Some(value) = entry.get("capital_events") else {
+        return Vec::new();
+    };
+    let Some(events) = value.as_array() else {
+        problems.push(format!("markets[{}] ({}): `capital_events` must be an array", index, name));
+        return Vec::new();
+    };
+
+    let mut parsed = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        let pair = event.as_array().filter(|pair| pair.len() == 2);
+        let step = pair.and_then(|pair| pair[0].as_u64());
+        let amount = pair.and_then(|pair| pair[1].as_f64()).filter(|a| a.is_finite());
+        match (step, amount) {
+            (Some(step), Some(amount)) => parsed.push((step, amount)),
+            _ => problems.push(format!(
+                "markets[{}] ({}): `capital_events[{}]` must be [step, amount]",
+                index, name, i
+            )),
+        }
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load `json` through a real file, named after the test
+    fn load(test: &str, json: &str) -> Result<HashMap<String, MarketState>, BotError> {
+        let path = std::env::temp_dir().join(format!("config_{}_{}.json", test, std::process::id()));
+        fs::write(&path, json).unwrap();
+        let loaded = load_markets(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    fn problems(loaded: Result<HashMap<String, MarketState>, BotError>) -> Vec<String> {
+        match loaded {
+            Err(BotError::Config(error)) => error.problems,
+            other => panic!("expected a config error, got {:?}", other.map(|m| m.len())),
+        }
+    }
+
+    #[test]
+    fn loads_a_capital_fraction_inventory_limit() {
+        let markets = load(
+            "capital_fraction",
+            r#"{"markets": [
+                {"name": "a", "initial_mid": 0.5, "capital": 1000, "inventory_limit_mode": {"capital_fraction": 0.2}},
+                {"name": "b", "initial_mid": 0.5, "inventory_limit_mode": "fixed"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(markets["a"].inventory_limit_mode, InventoryLimit::CapitalFraction { fraction: 0.2 });
+        assert_eq!(markets["b"].inventory_limit_mode, InventoryLimit::Fixed);
+    }
+
+    #[test]
+    fn rejects_bad_inventory_limit_modes() {
+        let problems = problems(load(
+            "bad_limit_mode",
+            r#"{"markets": [
+                {"name": "a", "initial_mid": 0.5, "capital": 1000, "inventory_limit_mode": {"capital_fraction": 1.5}},
+                {"name": "b", "initial_mid": 0.5, "inventory_limit_mode": {"capital_fraction": 0.5}},
+                {"name": "c", "initial_mid": 0.5, "inventory_limit_mode": "dynamic"}
+            ]}"#,
+        ));
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].contains("(a)") && problems[0].contains("must be in (0, 1]"));
+        assert!(problems[1].contains("(b)") && problems[1].contains("needs `capital` > 0"));
+        assert!(problems[2].contains("(c)"));
+    }
+
+    #[test]
+    fn loads_drift_and_rejects_out_of_range_drift() {
+        let markets = load("drift", r#"{"markets": [{"name": "a", "initial_mid": 0.5, "drift": 0.001}]}"#).unwrap();
+        assert_eq!(markets["a"].drift, 0.001);
+
+        let problems = problems(load("bad_drift", r#"{"markets": [{"name": "a", "initial_mid": 0.5, "drift": 2}]}"#));
+        assert_eq!(problems, vec!["markets[0] (a): `drift` = 2 must be in (-1, 1)".to_string()]);
+    }
+
+    #[test]
+    fn loads_accrue_rebates() {
+        let markets = load(
+            "accrue_rebates",
+            r#"{"markets": [{"name": "a", "initial_mid": 0.5, "fee": -0.001, "accrue_rebates": true}]}"#,
+        )
+        .unwrap();
+        assert!(markets["a"].accrue_rebates);
+
+        let problems = problems(load(
+            "bad_accrue_rebates",
+            r#"{"markets": [{"name": "a", "initial_mid": 0.5, "accrue_rebates": "yes"}]}"#,
+        ));
+        assert_eq!(problems, vec!["markets[0] (a): `accrue_rebates` must be true or false".to_string()]);
+    }
+
+    #[test]
+    fn loads_beta_and_maker_settings() {
+        let path = std::env::temp_dir().join(format!("config_makers_{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"markets": [
+                {"name": "a", "initial_mid": 0.5, "beta": -0.8, "price_impact": 0.002, "tick_size": 0.01,
+                 "ladder_levels": 3, "quoting_model": {"avellaneda_stoikov": {"gamma": 0.1, "sigma": 0.02, "k": 1.5}}},
+                {"name": "b", "initial_mid": 0.5}
+            ]}"#,
+        )
+        .unwrap();
+        let config = load_config(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.markets["a"].beta, -0.8);
+        let maker = &config.makers["a"];
+        assert_eq!(maker.price_impact, 0.002);
+        assert_eq!(maker.tick_size, Some(0.01));
+        assert_eq!(maker.ladder_levels, 3);
+        assert_eq!(maker.quoting_model, QuotingModel::AvellanedaStoikov { gamma: 0.1, sigma: 0.02, k: 1.5 });
+
+        let defaults = MarketMakerConfig::default();
+        assert_eq!(config.makers["b"].tick_size, defaults.tick_size);
+        assert_eq!(config.makers["b"].quoting_model, defaults.quoting_model);
+    }
+
+    #[test]
+    fn rejects_bad_maker_settings() {
+        let problems = problems(load(
+            "bad_makers",
+            r#"{"markets": [{"name": "a", "initial_mid": 0.5, "price_impact": -1, "tick_size": 1,
+                "ladder_levels": 0, "quoting_model": {"avellaneda_stoikov": {"gamma": 0, "sigma": 0.02}}}]}"#,
+        ));
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(problems.iter().any(|p| p.contains("`price_impact`")));
+        assert!(problems.iter().any(|p| p.contains("`tick_size`")));
+        assert!(problems.iter().any(|p| p.contains("`ladder_levels`")));
+        assert!(problems.iter().any(|p| p.contains("`gamma`")));
+        assert!(problems.iter().any(|p| p.contains("`k`")));
+    }
+}
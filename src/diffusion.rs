@@ -0,0 +1,173 @@
+use crate::error::BotError;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+/// Correlated mid diffusion: a covariance matrix over named markets, applied
+/// as one correlated Gaussian shock vector per step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "CovarianceSpec")]
+pub struct CovarianceModel {
+    pub markets: Vec<String>,
+    pub covariance: Vec<Vec<f64>>,
+    #[serde(skip)]
+    cholesky: Vec<Vec<f64>>,
+}
+
+/// Serialized form; the Cholesky factor is rebuilt (and validated) on load
+#[derive(Deserialize)]
+struct CovarianceSpec {
+    markets: Vec<String>,
+    covariance: Vec<Vec<f64>>,
+}
+
+impl TryFrom<CovarianceSpec> for CovarianceModel {
+    type Error = String;
+
+    fn try_from(spec: CovarianceSpec) -> Result<Self, Self::Error> {
+        CovarianceModel::new(spec.markets, spec.covariance).map_err(|e| e.to_string())
+    }
+}
+
+impl CovarianceModel {
+    /// Build the model, rejecting matrices that are not square, symmetric and
+    /// positive-semidefinite
+    pub fn new(markets: Vec<String>, covariance: Vec<Vec<f64>>) -> Result<Self, BotError> {
+        let n = markets.len();
+        if covariance.len() != n || covariance.iter().any(|row| row.len() != n) {
+            return Err(BotError::InvalidCovariance(format!(
+                "must be {}x{} to match the market list",
+                n, n
+            )));
+        }
+        for i in 0..n {
+            for j in 0..i {
+                if (covariance[i][j] - covariance[j][i]).abs() > 1e-12 {
+                    return Err(BotError::InvalidCovariance(format!(
+                        "not symmetric at ({}, {})",
+                        markets[i], markets[j]
+                    )));
+                }
+            }
+        }
+
+        let cholesky = cholesky(&covariance)
+            .ok_or_else(|| BotError::InvalidCovariance("not positive-semidefinite".to_string()))?;
+
+        Ok(CovarianceModel {
+            markets,
+            covariance,
+            cholesky,
+        })
+    }
+
+    /// Load `{ "markets": [...], "covariance": [[...], ...] }` from a JSON file
+    pub fn from_json_file(path: &str) -> Result<Self, BotError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Draw one correlated shock per market: `L * z` with `z ~ N(0, I)`
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> HashMap<String, f64> {
+        let z: Vec<f64> = (0..self.markets.len()).map(|_| standard_normal(rng)).collect();
+
+        self.markets
+            .iter()
+            .zip(&self.cholesky)
+            .map(|(name, row)| {
+                let shock: f64 = row.iter().zip(&z).map(|(l, z)| l * z).sum();
+                (name.clone(), shock)
+            })
+            .collect()
+    }
+}
+
+/// Lower-triangular Cholesky factor that tolerates singular (semidefinite)
+/// matrices by zeroing columns with a zero pivot. Returns None if the matrix
+/// has a negative pivot, i.e. it is not positive-semidefinite.
+fn cholesky(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    const TOLERANCE: f64 = 1e-12;
+    let n = matrix.len();
+    let mut lower = vec![vec![0.0; n]; n];
+
+    for j in 0..n {
+        let pivot = matrix[j][j] - (0..j).map(|k| lower[j][k] * lower[j][k]).sum::<f64>();
+        if pivot < -TOLERANCE {
+            return None;
+        }
+        if pivot <= TOLERANCE {
+            // Column is a combination of earlier ones; the rest must agree
+            for i in (j + 1)..n {
+                let residual = matrix[i][j] - (0..j).map(|k| lower[i][k] * lower[j][k]).sum::<f64>();
+                if residual.abs() > 1e-9 {
+                    return None;
+                }
+            }
+            continue;
+        }
+
+        let diag = pivot.sqrt();
+        lower[j][j] = diag;
+        for i in (j + 1)..n {
+            let residual = matrix[i][j] - (0..j).map(|k| lower[i][k] * lower[j][k]).sum::<f64>();
+            lower[i][j] = residual / diag;
+        }
+    }
+
+    Some(lower)
+}
+
+/// Standard normal draw via Box-Muller
+pub fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn sampled_shocks_show_the_configured_correlation() {
+        let (var_a, var_b, correlation): (f64, f64, f64) = (0.0004, 0.0009, 0.6);
+        let cov = correlation * (var_a * var_b).sqrt();
+        let model = CovarianceModel::new(
+            vec!["alpha".to_string(), "beta".to_string()],
+            vec![vec![var_a, cov], vec![cov, var_b]],
+        )
+        .unwrap();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(42);
+        let steps = 20_000;
+        let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for _ in 0..steps {
+            let shock = model.sample(&mut rng);
+            let (a, b) = (shock["alpha"], shock["beta"]);
+            sum_a += a;
+            sum_b += b;
+            sum_aa += a * a;
+            sum_bb += b * b;
+            sum_ab += a * b;
+        }
+
+        let n = steps as f64;
+        let cov_ab = sum_ab / n - (sum_a / n) * (sum_b / n);
+        let sd_a = (sum_aa / n - (sum_a / n).powi(2)).sqrt();
+        let sd_b = (sum_bb / n - (sum_b / n).powi(2)).sqrt();
+        let sampled = cov_ab / (sd_a * sd_b);
+
+        assert!((sampled - correlation).abs() < 0.03, "sampled correlation {sampled} vs {correlation}");
+        assert!((sd_a - var_a.sqrt()).abs() < 0.1 * var_a.sqrt());
+        assert!((sd_b - var_b.sqrt()).abs() < 0.1 * var_b.sqrt());
+    }
+
+    #[test]
+    fn an_asymmetric_matrix_is_rejected_as_invalid_covariance() {
+        let markets = vec!["a".to_string(), "b".to_string()];
+        let result = CovarianceModel::new(markets, vec![vec![1.0, 0.5], vec![0.2, 1.0]]);
+        assert!(matches!(result, Err(BotError::InvalidCovariance(_))));
+    }
+}
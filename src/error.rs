@@ -0,0 +1,81 @@
+use crate::config::ConfigError;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Failures in engine construction, settings and lookups, config loading
+/// and output handling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotError {
+    /// No market by this name
+    UnknownMarket(String),
+    /// The market exists but lacks its maker or order-flow RNG, e.g. after
+    /// being inserted into `markets` directly instead of at construction
+    IncompleteMarket { market: String, missing: &'static str },
+    /// An output path that is not valid UTF-8
+    NonUtf8Path(PathBuf),
+    /// A market config file that could not be loaded
+    Config(ConfigError),
+    /// Markets with NaN or infinite fields, by name, with the fields
+    NonFiniteMarkets(Vec<(String, Vec<&'static str>)>),
+    /// An orders-per-tick range whose minimum exceeds its maximum
+    InvalidOrdersPerTick { min: u32, max: u32 },
+    /// No outcome group by this name
+    UnknownGroup(String),
+    /// A settlement winner that is not a member of the group
+    NotInGroup { group: String, market: String },
+    /// A market that has already resolved
+    AlreadySettled(String),
+    /// An RNG position missing a market's flow or maker stream
+    MissingRngPosition(String),
+    /// `write_checkpoint` called with no checkpoint dir configured
+    CheckpointNotConfigured,
+    /// A covariance matrix that is not square, symmetric and PSD
+    InvalidCovariance(String),
+    /// A wrapped I/O, JSON or CSV error, kept as its message so the enum
+    /// stays `Clone + Eq`
+    Io(String),
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::UnknownMarket(name) => write!(f, "unknown market: {}", name),
+            BotError::IncompleteMarket { market, missing } => write!(f, "market {} has no {}", market, missing),
+            BotError::NonUtf8Path(path) => write!(f, "path is not valid UTF-8: {}", path.display()),
+            BotError::Config(error) => error.fmt(f),
+            BotError::NonFiniteMarkets(markets) => {
+                let described: Vec<String> = markets
+                    .iter()
+                    .map(|(name, fields)| format!("{} ({})", name, fields.join(", ")))
+                    .collect();
+                write!(f, "non-finite market values: {}", described.join("; "))
+            }
+            BotError::InvalidOrdersPerTick { min, max } => {
+                write!(f, "min orders per tick {} exceeds max {}", min, max)
+            }
+            BotError::UnknownGroup(group) => write!(f, "unknown outcome group: {}", group),
+            BotError::NotInGroup { group, market } => {
+                write!(f, "{} is not a member of outcome group {}", market, group)
+            }
+            BotError::AlreadySettled(market) => write!(f, "market already settled: {}", market),
+            BotError::MissingRngPosition(market) => write!(f, "rng position has no entry for market {}", market),
+            BotError::CheckpointNotConfigured => write!(f, "checkpointing is not configured"),
+            BotError::InvalidCovariance(reason) => write!(f, "invalid covariance matrix: {}", reason),
+            BotError::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+impl From<std::io::Error> for BotError {
+    fn from(error: std::io::Error) -> Self {
+        BotError::Io(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BotError {
+    fn from(error: serde_json::Error) -> Self {
+        BotError::Io(error.to_string())
+    }
+}
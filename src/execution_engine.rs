@@ -1,17 +1,46 @@
-use crate::market_maker::{FillResult, MarketMaker, Order};
-use crate::market_state::MarketState;
+use crate::diffusion::{standard_normal, CovarianceModel};
+use crate::error::BotError;
+use crate::historical_flow::HistoricalFlow;
+use crate::interrupt;
+use crate::logger::TraceWriter;
+use crate::market_maker::{EffectiveConfig, FillResult, MarketMaker, MarketMakerConfig, Order, Quote, QuoteRationale};
+use crate::market_state::{calmar_ratio, Fill, HaltReason, MarketSeries, MarketSnapshot, MarketState};
+use crate::metrics::PerformanceReport;
+use crate::order_flow::{self, OrderFlow, OrderFlowModel};
+use crate::price_feed::PolledFeed;
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepResult {
+    pub step: u64, // engine time of the step, so streamed records are self-locating
     pub fills: Vec<FillInfo>,
     pub mid: f64,
     pub inventory: f64,
     pub pnl: f64,
     pub spread: f64,
+    pub stuck: bool, // forced unwind triggered at the inventory limit
+    #[serde(default)]
+    pub frozen: bool, // halted by a session PnL cap or capital exhaustion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub halt_reason: Option<HaltReason>,
+    #[serde(default)]
+    pub halted: bool, // unwinding after a drawdown-limit breach
+    pub inventory_utilization: Option<f64>, // percent of limit, None if the limit is zero
+    pub exposure_utilization: Option<f64>,
+    // maker config and derived values that produced this step's quotes,
+    // when auditing is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<EffectiveConfig>,
+    // why the maker quoted as it did, when explaining is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<QuoteRationale>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +50,16 @@ pub struct FillInfo {
     pub price: f64,
 }
 
+impl From<&Fill> for FillInfo {
+    fn from(fill: &Fill) -> Self {
+        FillInfo {
+            side: fill.side.clone(),
+            size: fill.size,
+            price: fill.price,
+        }
+    }
+}
+
 impl From<&FillResult> for FillInfo {
     fn from(fill: &FillResult) -> Self {
         FillInfo {
@@ -31,15 +70,283 @@ impl From<&FillResult> for FillInfo {
     }
 }
 
+/// A step's results keyed in market order, so serialized traces are
+/// byte-identical across identically seeded runs
+pub fn by_market(results: &HashMap<String, StepResult>) -> BTreeMap<&str, &StepResult> {
+    results.iter().map(|(name, result)| (name.as_str(), result)).collect()
+}
+
+/// Derive a market's RNG seed from the master seed and its name (FNV-1a),
+/// so each market's flow does not depend on the order markets are visited
+pub fn derive_market_seed(master_seed: u64, market_name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325 ^ master_seed;
+    for byte in market_name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Word positions of every RNG stream in the engine, enough to fast-forward
+/// a fresh engine built with the same seeds without replaying steps. Keyed
+/// in market order so `summary.json` is stable across runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RngPosition {
+    pub flow: BTreeMap<String, u128>,
+    pub makers: BTreeMap<String, u128>,
+    pub diffusion: u128,
+}
+
+/// Wall-clock cost of a `run_timed` call
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+    pub total_steps: usize,
+    pub elapsed: Duration,
+    pub steps_per_sec: f64,
+    pub slowest_step: Duration,
+}
+
+/// Move `prices` evenly until their sum reaches `target`, raising asks up
+/// to it or lowering bids down to it, never past [0, 1]. A leg that pins at
+/// the bound passes the rest of its share to the others; returns which legs
+/// the move pinned
+fn spread_to_total(prices: &mut [f64], target: f64, raise: bool) -> Vec<bool> {
+    const TOLERANCE: f64 = 1e-12;
+    let bound = if raise { 1.0 } else { 0.0 };
+    let mut pinned = vec![false; prices.len()];
+    loop {
+        let total: f64 = prices.iter().sum();
+        let gap = if raise { target - total } else { total - target };
+        let open: Vec<usize> = (0..prices.len()).filter(|&i| prices[i] != bound).collect();
+        if gap <= TOLERANCE || open.is_empty() {
+            return pinned;
+        }
+        let share = gap / open.len() as f64;
+        for i in open {
+            prices[i] = if raise { (prices[i] + share).min(1.0) } else { (prices[i] - share).max(0.0) };
+            pinned[i] = prices[i] == bound;
+        }
+    }
+}
+
+/// Terminal accounting entry for one settled market
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementRecord {
+    pub market: String,
+    pub step: u64,
+    pub outcome: bool,
+    pub inventory: f64,        // position carried into settlement
+    pub settlement_price: f64, // 1.0 for YES, 0.0 for NO
+    pub pre_settlement_realized_pnl: f64, // realized before the payout
+    pub settlement_pnl: f64,   // realized on settling, including claimed rebates
+}
+
+/// Periodic full-state snapshots so a crashed run can be resumed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointConfig {
+    pub interval: u64,
+    pub dir: PathBuf,
+}
+
+/// What engine construction does with a market whose mid or limits are
+/// NaN/infinite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NonFinitePolicy {
+    /// Fail construction, naming every bad market
+    Reject,
+    /// Drop the bad markets with a warning and run the rest
+    Skip,
+}
+
+/// What the engine does once capital accounting shows no usable capital
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapitalExhaustion {
+    /// Keep quoting regardless
+    Ignore,
+    /// Flatten and halt each market whose own capital is used up
+    HaltMarket,
+    /// Flatten and halt every market once the summed capital is used up
+    HaltPortfolio,
+}
+
+fn default_capital_exhaustion() -> CapitalExhaustion {
+    CapitalExhaustion::HaltMarket
+}
+
+/// Names of the fields of `state` that are not finite
+fn non_finite_fields(state: &MarketState) -> Vec<&'static str> {
+    [
+        ("mid", state.mid),
+        ("inventory_limit", state.inventory_limit),
+        ("exposure_limit", state.exposure_limit),
+        ("capital", state.capital),
+        ("reversion_rate", state.reversion_rate),
+        ("reversion_anchor", state.reversion_anchor),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_finite())
+    .map(|(field, _)| field)
+    .collect()
+}
+
+/// Check every market's mid and limits are finite; under `Skip` the bad
+/// markets are dropped with a warning
+fn validate_finite(
+    mut markets: HashMap<String, MarketState>,
+    policy: NonFinitePolicy,
+) -> Result<HashMap<String, MarketState>, BotError> {
+    let mut bad: Vec<(String, Vec<&'static str>)> = markets
+        .iter()
+        .map(|(name, state)| (name.clone(), non_finite_fields(state)))
+        .filter(|(_, fields)| !fields.is_empty())
+        .collect();
+    if bad.is_empty() {
+        return Ok(markets);
+    }
+    bad.sort();
+
+    match policy {
+        NonFinitePolicy::Reject => Err(BotError::NonFiniteMarkets(bad)),
+        NonFinitePolicy::Skip => {
+            let described: Vec<String> = bad
+                .iter()
+                .map(|(name, fields)| format!("{} ({})", name, fields.join(", ")))
+                .collect();
+            eprintln!("⚠️  Skipping markets with non-finite values: {}", described.join("; "));
+            for (name, _) in &bad {
+                markets.remove(name);
+            }
+            Ok(markets)
+        }
+    }
+}
+
+fn default_flow_model() -> Box<dyn OrderFlow> {
+    Box::new(OrderFlowModel::MidBiased)
+}
+
+fn default_orders_per_tick() -> (u32, u32) {
+    (1, 3)
+}
+
+/// Shared factor shock std dev per step when `set_correlation` enables it
+pub const DEFAULT_FACTOR_VOL: f64 = 0.01;
+
+/// One step per trading day
+fn default_steps_per_year() -> f64 {
+    252.0
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct ExecutionEngine {
     pub markets: HashMap<String, MarketState>,
     pub market_makers: HashMap<String, MarketMaker>,
     pub time: u64,
-    pub rng: ChaCha8Rng,
+    pub rng_seed: u64, // master seed the per-market flow seeds derive from
+    pub rngs: HashMap<String, ChaCha8Rng>,
+    pub price_seed: u64, // seed of the mid-diffusion shocks, independent of flow
+    pub markout_horizon: u64, // steps after a fill at which its markout is taken
+    // taker flow for every market, unless overridden per market
+    #[serde(default = "default_flow_model", with = "order_flow::boxed")]
+    pub flow_model: Box<dyn OrderFlow>,
+    #[serde(default, with = "order_flow::boxed_map")]
+    pub flow_models: HashMap<String, Box<dyn OrderFlow>>,
+    // (min, max) orders per tick for the uniform flow models; set through
+    // set_orders_per_tick, which checks min <= max
+    #[serde(default = "default_orders_per_tick")]
+    orders_per_tick: (u32, u32),
+    // recorded taker flow replayed instead of the flow models when present
+    #[serde(default)]
+    pub historical_flow: Option<HistoricalFlow>,
+    pub covariance: Option<CovarianceModel>,
+    // std dev of a shared per-step shock that moves each mid by beta times it
+    #[serde(default)]
+    pub correlation_factor: Option<f64>,
+    // external reference mids that replace the synthetic drift when present
+    #[serde(default)]
+    pub price_feed: Option<PolledFeed>,
+    pub diffusion_rng: ChaCha8Rng,
+    pub checkpoint: Option<CheckpointConfig>,
+    // copy each maker's config into its step results
+    #[serde(default)]
+    pub audit_config: bool,
+    // attach each maker's quote rationale to its step results
+    #[serde(default)]
+    pub explain_quotes: bool,
+    // annualization for the Calmar ratio
+    #[serde(default = "default_steps_per_year")]
+    pub steps_per_year: f64,
+    // steps run before PnL, drawdown and fill stats count toward the
+    // reported metrics; the simulation itself runs normally through them
+    #[serde(default)]
+    pub warmup_steps: u64,
+    // riskless PnL per step for Sharpe and Sortino
+    #[serde(default)]
+    pub risk_free: f64,
+    // drawdown of the summed PnL, which differs from the worst single market
+    #[serde(default)]
+    pub portfolio_peak_pnl: f64,
+    #[serde(default)]
+    pub portfolio_max_drawdown: f64,
+    // mutually-exclusive outcome groups: exactly one member resolves YES
+    #[serde(default)]
+    pub outcome_groups: HashMap<String, Vec<String>>,
+    // shift each group's quotes so our asks sum to >= 1 and bids to <= 1
+    #[serde(default)]
+    pub reconcile_groups: bool,
+    #[serde(default)]
+    pub settlement_ledger: Vec<SettlementRecord>,
+    #[serde(default = "default_capital_exhaustion")]
+    pub capital_exhaustion: CapitalExhaustion,
+    // (fill index, due step) per market, oldest first
+    #[serde(default)]
+    pending_markouts: HashMap<String, VecDeque<(usize, u64)>>,
+    // scratch space for each tick's order flow, reused to avoid reallocating
+    #[serde(skip)]
+    order_buffer: Vec<Order>,
+    #[serde(skip)]
+    quote_buffer: HashMap<String, Option<Quote>>,
+    #[serde(skip)]
+    ladder_buffer: Vec<Quote>,
+    // market names in sorted order, rebuilt only when the market set changes
+    #[serde(skip)]
+    market_order: Vec<String>,
 }
 
 impl ExecutionEngine {
-    pub fn new(markets: HashMap<String, MarketState>, rng_seed: u64) -> Self {
+    /// Markets with a preset `rng_seed` keep it, which lets a single market's
+    /// flow from a larger run be reproduced in a one-market engine
+    pub fn new(markets: HashMap<String, MarketState>, rng_seed: u64) -> Result<Self, BotError> {
+        Self::with_seeds(markets, rng_seed, rng_seed)
+    }
+
+    /// Seed order flow and mid diffusion separately, so either source of
+    /// randomness can be held fixed while the other varies
+    pub fn with_seeds(markets: HashMap<String, MarketState>, flow_seed: u64, price_seed: u64) -> Result<Self, BotError> {
+        Self::with_policy(markets, flow_seed, price_seed, NonFinitePolicy::Reject)
+    }
+
+    /// Like `with_seeds`, choosing whether markets with a NaN or infinite mid
+    /// or limit fail construction or are dropped
+    pub fn with_policy(
+        markets: HashMap<String, MarketState>,
+        flow_seed: u64,
+        price_seed: u64,
+        policy: NonFinitePolicy,
+    ) -> Result<Self, BotError> {
+        let markets = validate_finite(markets, policy)?;
+        Ok(Self::build(markets, flow_seed, price_seed))
+    }
+
+    fn build(mut markets: HashMap<String, MarketState>, flow_seed: u64, price_seed: u64) -> Self {
+        let mut rngs = HashMap::new();
+        for (name, state) in markets.iter_mut() {
+            let seed = *state
+                .rng_seed
+                .get_or_insert_with(|| derive_market_seed(flow_seed, name));
+            rngs.insert(name.clone(), ChaCha8Rng::seed_from_u64(seed));
+        }
+
         let market_makers: HashMap<String, MarketMaker> = markets
             .iter()
             .map(|(name, state)| (name.clone(), MarketMaker::new(state, None)))
@@ -49,93 +356,1423 @@ impl ExecutionEngine {
             markets,
             market_makers,
             time: 0,
-            rng: ChaCha8Rng::seed_from_u64(rng_seed),
+            rng_seed: flow_seed,
+            rngs,
+            price_seed,
+            markout_horizon: 5,
+            flow_model: default_flow_model(),
+            flow_models: HashMap::new(),
+            orders_per_tick: default_orders_per_tick(),
+            historical_flow: None,
+            covariance: None,
+            correlation_factor: None,
+            price_feed: None,
+            diffusion_rng: ChaCha8Rng::seed_from_u64(derive_market_seed(price_seed, "__diffusion__")),
+            checkpoint: None,
+            audit_config: false,
+            explain_quotes: false,
+            steps_per_year: default_steps_per_year(),
+            portfolio_peak_pnl: 0.0,
+            portfolio_max_drawdown: 0.0,
+            outcome_groups: HashMap::new(),
+            reconcile_groups: false,
+            settlement_ledger: Vec::new(),
+            warmup_steps: 0,
+            risk_free: 0.0,
+            capital_exhaustion: default_capital_exhaustion(),
+            pending_markouts: HashMap::new(),
+            order_buffer: Vec::new(),
+            quote_buffer: HashMap::new(),
+            ladder_buffer: Vec::new(),
+            market_order: Vec::new(),
         }
     }
 
-    /// Simulate random market order flow for a given market
-    fn simulate_order_flow(&mut self, market_name: &str) -> Vec<Order> {
-        let state = self.markets.get(market_name).unwrap();
-        let mut orders = Vec::new();
-        
-        // Generate 1-3 orders per tick
-        let n = self.rng.gen_range(1..=3);
-        
-        for _ in 0..n {
-            // Bias toward mid: higher mid -> more buys, lower mid -> more sells
-            let noise: f64 = self.rng.gen_range(-0.15..0.15);
-            let prob = state.mid + noise;
-            
-            let side = if prob > 0.5 { "buy" } else { "sell" };
-            
-            // Size follows a normal-ish distribution clamped to [1, 30]
-            let size: f64 = (self.rng.gen::<f64>() * 4.0 + 4.0).max(1.0).min(30.0);
-            
-            // Price: buyers willing to pay 1.0, sellers accept 0.0
-            let price = if side == "buy" { 1.0 } else { 0.0 };
-            
-            orders.push(Order {
-                side: side.to_string(),
-                size,
-                price,
+    /// A new engine over `markets`, seeded with `seed`, that simulates like
+    /// this one: the same flow models, orders per tick, replay, diffusion,
+    /// group handling, warm-up and risk settings, plus the maker configs and
+    /// betas of same-named markets. The price feed and checkpointing stay
+    /// off, so Monte Carlo runs neither poll nor overwrite checkpoints.
+    /// `markets` are taken as already validated, e.g. the ones this engine
+    /// was built from
+    pub fn fresh_run(&self, markets: HashMap<String, MarketState>, seed: u64) -> Self {
+        let mut engine = Self::build(markets, seed, seed);
+        for (name, state) in engine.markets.iter_mut() {
+            if let Some(configured) = self.markets.get(name) {
+                state.beta = configured.beta;
+            }
+            if let (Some(mm), Some(configured)) = (engine.market_makers.get_mut(name), self.market_makers.get(name)) {
+                *mm = MarketMaker::new(state, Some(configured.config.clone()));
+            }
+        }
+        engine.markout_horizon = self.markout_horizon;
+        engine.flow_model = self.flow_model.clone_box();
+        engine.flow_models = self
+            .flow_models
+            .iter()
+            .map(|(name, flow)| (name.clone(), flow.clone_box()))
+            .collect();
+        engine.orders_per_tick = self.orders_per_tick;
+        engine.historical_flow = self.historical_flow.clone();
+        engine.covariance = self.covariance.clone();
+        engine.correlation_factor = self.correlation_factor;
+        engine.steps_per_year = self.steps_per_year;
+        engine.warmup_steps = self.warmup_steps;
+        engine.risk_free = self.risk_free;
+        engine.outcome_groups = self.outcome_groups.clone();
+        engine.reconcile_groups = self.reconcile_groups;
+        engine.capital_exhaustion = self.capital_exhaustion;
+        engine
+    }
+
+    /// Replay the taker orders recorded in `csv_path` instead of simulating
+    /// flow; `run` stops once the recording runs out
+    pub fn from_historical(markets: HashMap<String, MarketState>, csv_path: &str) -> Result<Self, BotError> {
+        let mut engine = Self::new(markets, 0)?;
+        let flow = HistoricalFlow::from_csv_path(csv_path).map_err(|e| BotError::Io(e.to_string()))?;
+        engine.historical_flow = Some(flow);
+        Ok(engine)
+    }
+
+    /// True once a historical replay has no ticks left; always false for
+    /// simulated flow
+    pub fn replay_exhausted(&self) -> bool {
+        self.historical_flow
+            .as_ref()
+            .is_some_and(|flow| flow.exhausted(self.time))
+    }
+
+    /// Bound the taker orders the uniform flow models send per tick, to
+    /// simulate thin or heavy flow
+    pub fn set_orders_per_tick(&mut self, min: u32, max: u32) -> Result<(), BotError> {
+        if min > max {
+            return Err(BotError::InvalidOrdersPerTick { min, max });
+        }
+        self.orders_per_tick = (min, max);
+        Ok(())
+    }
+
+    pub fn orders_per_tick(&self) -> (u32, u32) {
+        self.orders_per_tick
+    }
+
+    /// Load markets on the shared factor by their betas, enabling it at the
+    /// default volatility if it is off. Unlisted markets keep their beta
+    pub fn set_correlation(&mut self, betas: &HashMap<String, f64>) -> Result<(), BotError> {
+        for name in betas.keys() {
+            self.market(name)?;
+        }
+        for (name, beta) in betas {
+            if let Some(state) = self.markets.get_mut(name) {
+                state.beta = *beta;
+            }
+        }
+        self.correlation_factor.get_or_insert(DEFAULT_FACTOR_VOL);
+        Ok(())
+    }
+
+    /// Rebuild a market's maker from `config`, e.g. one loaded by
+    /// `config::load_config`. The base spread still comes from the market,
+    /// and the maker's quoting history starts over
+    pub fn set_maker_config(&mut self, market_name: &str, config: MarketMakerConfig) -> Result<(), BotError> {
+        let state = self.market(market_name)?;
+        let maker = MarketMaker::new(state, Some(config));
+        self.market_makers.insert(market_name.to_string(), maker);
+        Ok(())
+    }
+
+    pub fn market(&self, market_name: &str) -> Result<&MarketState, BotError> {
+        self.markets
+            .get(market_name)
+            .ok_or_else(|| BotError::UnknownMarket(market_name.to_string()))
+    }
+
+    /// A market's mid, inventory, PnL and spread over every step it has
+    /// traded, without pivoting the trace
+    pub fn series(&self, market_name: &str) -> Option<MarketSeries> {
+        self.markets.get(market_name).map(|state| state.series.clone())
+    }
+
+    pub fn market_maker(&self, market_name: &str) -> Result<&MarketMaker, BotError> {
+        self.market(market_name)?;
+        self.market_makers.get(market_name).ok_or_else(|| BotError::IncompleteMarket {
+            market: market_name.to_string(),
+            missing: "market maker",
+        })
+    }
+
+    /// Check a market has everything `step` needs to trade it
+    fn check_tradable(&self, market_name: &str) -> Result<(), BotError> {
+        self.market_maker(market_name)?;
+        if !self.rngs.contains_key(market_name) {
+            return Err(BotError::IncompleteMarket {
+                market: market_name.to_string(),
+                missing: "order-flow RNG",
             });
         }
-        
-        orders
+        Ok(())
+    }
+
+    /// Rebuild the cached sorted market names if markets were added or
+    /// removed since the last step
+    fn refresh_market_order(&mut self) {
+        let stale = self.market_order.len() != self.markets.len()
+            || self.market_order.iter().any(|name| !self.markets.contains_key(name));
+        if stale {
+            self.market_order = self.markets.keys().cloned().collect();
+            self.market_order.sort();
+        }
+    }
+
+    /// Simulate random market order flow for a given market into `orders`,
+    /// or replay it when a historical flow is loaded
+    fn simulate_order_flow(&mut self, market_name: &str, orders: &mut Vec<Order>) -> Result<(), BotError> {
+        let state = self
+            .markets
+            .get(market_name)
+            .ok_or_else(|| BotError::UnknownMarket(market_name.to_string()))?;
+        let rng = self.rngs.get_mut(market_name).ok_or_else(|| BotError::IncompleteMarket {
+            market: market_name.to_string(),
+            missing: "order-flow RNG",
+        })?;
+        if let Some(flow) = &self.historical_flow {
+            flow.orders_at(self.time, market_name, orders);
+            return Ok(());
+        }
+        let flow = match self.flow_models.get_mut(market_name) {
+            Some(flow) => flow,
+            None => &mut self.flow_model,
+        };
+        flow.generate(state, rng, self.orders_per_tick, orders);
+        Ok(())
     }
 
     /// Execute one simulation step across all markets
     pub fn step(&mut self) -> HashMap<String, StepResult> {
-        let mut results = HashMap::new();
+        // Each market draws from its own RNG stream, so order doesn't change
+        // the draws; sorting just makes side effects like logs reproducible.
+        // The sorted names and per-step scratch buffers live on the engine
+        // and are taken for the step, so a tick allocates little
+        self.refresh_market_order();
+        let market_order = std::mem::take(&mut self.market_order);
+        let mut quotes = std::mem::take(&mut self.quote_buffer);
+        let mut orders = std::mem::take(&mut self.order_buffer);
+        let mut ladder = std::mem::take(&mut self.ladder_buffer);
         
-        let market_names: Vec<String> = self.markets.keys().cloned().collect();
+        // One correlated shock vector per step, shared across markets
+        let shocks = match &self.covariance {
+            Some(model) => model.sample(&mut self.diffusion_rng),
+            None => HashMap::new(),
+        };
+        let factor_shock = match self.correlation_factor {
+            Some(vol) => vol * standard_normal(&mut self.diffusion_rng),
+            None => 0.0,
+        };
+        if let Some(feed) = self.price_feed.as_mut() {
+            feed.poll(self.time);
+        }
         
-        for name in market_names {
-            let orders = self.simulate_order_flow(&name);
+        // Settled markets no longer trade, and incomplete ones cannot
+        let market_names: Vec<&String> = market_order
+            .iter()
+            .filter(|name| self.markets[*name].resolved.is_none())
+            .filter(|name| match self.check_tradable(name) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("⚠️  Skipping {} at step {}: {}", name, self.time, e);
+                    false
+                }
+            })
+            .collect();
+        let mut results = HashMap::with_capacity(market_names.len());
+        
+        // Quote every market first so outcome groups can be reconciled
+        // before any order hits them. Old entries are blanked, not removed,
+        // so their keys are reused
+        quotes.values_mut().for_each(|quote| *quote = None);
+        for &name in &market_names {
+            let (Some(state), Some(mm)) = (self.markets.get_mut(name), self.market_makers.get_mut(name)) else {
+                continue;
+            };
+            state.apply_capital_events(self.time);
+            mm.time = self.time;
+            // Auditing reads the derived values off the rationale
+            mm.explain = self.explain_quotes || self.audit_config;
+            let quote = if state.frozen { None } else { mm.quote(state) };
+            match quotes.get_mut(name) {
+                Some(slot) => *slot = quote,
+                None => {
+                    quotes.insert(name.clone(), quote);
+                }
+            }
+        }
+        if self.reconcile_groups {
+            self.reconcile_group_quotes(&mut quotes);
+        }
+        
+        for &name in &market_names {
+            if let Err(e) = self.simulate_order_flow(name, &mut orders) {
+                eprintln!("⚠️  Skipping {} at step {}: {}", name, self.time, e);
+                continue;
+            }
             
             // Get mutable references
-            let state = self.markets.get_mut(&name).unwrap();
-            let mm = self.market_makers.get_mut(&name).unwrap();
+            let (Some(state), Some(mm)) = (self.markets.get_mut(name), self.market_makers.get_mut(name)) else {
+                continue;
+            };
             
-            let fills = mm.on_tick(state, &orders);
+            let previous_mid = state.mid;
+            let first_new_fill = state.fills.len();
+            match quotes.get_mut(name).and_then(Option::take) {
+                // A halted market unwinds through its inside quote alone
+                Some(inside) if state.halted => mm.ladder_into(inside, 1, &mut ladder),
+                Some(inside) => mm.ladder_into(inside, mm.config.ladder_levels, &mut ladder),
+                None => ladder.clear(),
+            }
+            let mut fills: Vec<FillInfo> = mm.on_tick(state, &orders, &ladder).iter().map(FillInfo::from).collect();
             
-            // Update PnL for each fill
-            for fill in &fills {
-                let signed = if fill.side == "buy" { fill.size } else { -fill.size };
-                let prev_mid = state.mid;
-                state.pnl += -signed * (fill.price - prev_mid);
-                state.peak_pnl = state.peak_pnl.max(state.pnl);
-                let dd = state.peak_pnl - state.pnl;
-                state.max_drawdown = state.max_drawdown.max(dd);
+            let reference = self
+                .price_feed
+                .as_ref()
+                .and_then(|feed| feed.reference(name).map(|mid| (mid, feed.reversion)));
+            if let Some((reference, reversion)) = reference {
+                // Track the external reference price
+                state.mid = (state.mid + reversion * (reference - state.mid)).clamp(0.01, 0.99);
+            } else {
+                // Small mean reversion toward the market's anchor, which
+                // trends with its drift
+                let anchor = (state.reversion_anchor + state.drift * self.time as f64).clamp(0.01, 0.99);
+                let rate = state.reversion_rate;
+                state.mid = (state.mid * (1.0 - rate) + anchor * rate + state.drift).clamp(0.01, 0.99);
+            }
+            if let Some(shock) = shocks.get(name) {
+                state.mid = (state.mid + shock).clamp(0.01, 0.99);
+            }
+            if state.beta != 0.0 {
+                state.mid = (state.mid + state.beta * factor_shock).clamp(0.01, 0.99);
             }
             
-            // Small mean reversion toward 0.5
-            state.mid = state.mid * 0.995 + 0.5 * 0.005;
+            // PnL and exposure are marked to the post-move mid each step
+            state.update_volatility(previous_mid);
+            state.mark_to_market();
+            state.sample_inventory();
+            state.sample_pnl();
+            if state.check_session_caps() {
+                eprintln!("🧊 {} frozen at step {}: realized pnl {:.4} hit a session cap", name, self.time, state.realized_pnl);
+            }
+            if state.check_drawdown_limit() {
+                eprintln!("🛑 {} halted at step {}: drawdown {:.4} breached its limit, unwinding", name, self.time, state.max_drawdown);
+            }
+            if self.capital_exhaustion == CapitalExhaustion::HaltMarket && !state.frozen && state.capital_exhausted() {
+                fills.extend(state.flatten_and_halt(self.time, HaltReason::CapitalExhausted).as_ref().map(FillInfo::from));
+                eprintln!("🛑 {} out of capital at step {}: flattened and halted", name, self.time);
+            }
+            if !fills.is_empty() {
+                state.active_steps += 1;
+            }
             
+            // Queue this tick's fills and resolve markouts that have come due
+            let due = self.time + self.markout_horizon;
+            let pending = match self.pending_markouts.get_mut(name) {
+                Some(pending) => pending,
+                None => self.pending_markouts.entry(name.clone()).or_default(),
+            };
+            pending.extend((first_new_fill..state.fills.len()).map(|index| (index, due)));
+            while let Some(&(index, due)) = pending.front() {
+                if due > self.time {
+                    break;
+                }
+                let markout = state.record_markout(index);
+                mm.record_markout(markout);
+                pending.pop_front();
+            }
+            
+            state.record_series();
+            let rationale = mm.rationale.take();
             results.insert(
-                name,
+                name.clone(),
                 StepResult {
-                    fills: fills.iter().map(FillInfo::from).collect(),
+                    step: self.time,
+                    fills,
                     mid: state.mid,
                     inventory: state.inventory,
                     pnl: state.pnl,
                     spread: state.spread,
+                    stuck: mm.stuck,
+                    frozen: state.frozen,
+                    halt_reason: state.halt_reason,
+                    halted: state.halted,
+                    inventory_utilization: state.inventory_utilization(),
+                    exposure_utilization: state.exposure_utilization(),
+                    config: self.audit_config.then(|| EffectiveConfig::new(&mm.config, rationale.as_ref())),
+                    rationale: rationale.filter(|_| self.explain_quotes),
                 },
             );
         }
         
-        self.time += 1;
+        self.order_buffer = orders;
+        self.quote_buffer = quotes;
+        self.ladder_buffer = ladder;
+        self.market_order = market_order;
+        if self.capital_exhaustion == CapitalExhaustion::HaltPortfolio {
+            self.halt_portfolio_if_exhausted(&mut results);
+        }
+        
+        // time is the logical clock; saturating rather than wrapping keeps
+        // records in order, and u64::MAX steps is unreachable in practice
+        self.time = self.time.saturating_add(1);
+        self.settle_due_markets(&mut results);
+        
+        let total_pnl = self.total_pnl();
+        self.portfolio_peak_pnl = self.portfolio_peak_pnl.max(total_pnl);
+        self.portfolio_max_drawdown = self
+            .portfolio_max_drawdown
+            .max(self.portfolio_peak_pnl - total_pnl);
+        if self.warmup_steps > 0 && self.time == self.warmup_steps {
+            self.end_warmup();
+        }
         results
     }
 
+    /// Restart the reported metrics of every market and the portfolio
+    fn end_warmup(&mut self) {
+        for state in self.markets.values_mut() {
+            state.end_warmup();
+        }
+        self.portfolio_peak_pnl = self.total_pnl();
+        self.portfolio_max_drawdown = 0.0;
+    }
+
+    /// Summed PnL since the warm-up ended
+    pub fn reported_pnl(&self) -> f64 {
+        self.states_in_order().iter().map(|s| s.reported_pnl()).sum()
+    }
+
+    pub fn rng_position(&self) -> RngPosition {
+        RngPosition {
+            flow: self.rngs.iter().map(|(name, rng)| (name.clone(), rng.get_word_pos())).collect(),
+            makers: self
+                .market_makers
+                .iter()
+                .map(|(name, mm)| (name.clone(), mm.rng.get_word_pos()))
+                .collect(),
+            diffusion: self.diffusion_rng.get_word_pos(),
+        }
+    }
+
+    /// Move every RNG stream to `position`. Market state is untouched, so
+    /// pair this with a checkpoint to resume mid-run.
+    pub fn set_rng_position(&mut self, position: &RngPosition) -> Result<(), BotError> {
+        for name in self.rngs.keys() {
+            if !position.flow.contains_key(name) || !position.makers.contains_key(name) {
+                return Err(BotError::MissingRngPosition(name.clone()));
+            }
+        }
+        
+        for (name, rng) in self.rngs.iter_mut() {
+            rng.set_word_pos(position.flow[name]);
+        }
+        for (name, mm) in self.market_makers.iter_mut() {
+            mm.rng.set_word_pos(position.makers[name]);
+        }
+        self.diffusion_rng.set_word_pos(position.diffusion);
+        Ok(())
+    }
+
+    /// Flatten and halt every live market once the portfolio's summed
+    /// capital is used up, updating this step's results to match
+    fn halt_portfolio_if_exhausted(&mut self, results: &mut HashMap<String, StepResult>) {
+        let live = || self.markets.values().filter(|s| s.resolved.is_none() && !s.frozen);
+        let capital: f64 = live().map(|s| s.capital).sum();
+        let available: f64 = live().map(|s| s.available_capital()).sum();
+        if capital <= 0.0 || available > 0.0 {
+            return;
+        }
+
+        eprintln!("🛑 Portfolio out of capital at step {}: flattening and halting every market", self.time);
+        for (name, state) in self.markets.iter_mut() {
+            if state.resolved.is_some() || state.frozen {
+                continue;
+            }
+            let closing = state.flatten_and_halt(self.time, HaltReason::CapitalExhausted);
+            if closing.is_some() {
+                let index = state.fills.len() - 1;
+                let due = self.time + self.markout_horizon;
+                self.pending_markouts.entry(name.clone()).or_default().push_back((index, due));
+            }
+            if let Some(result) = results.get_mut(name) {
+                result.fills.extend(closing.as_ref().map(FillInfo::from));
+                result.inventory = state.inventory;
+                result.pnl = state.pnl;
+                result.frozen = true;
+                result.halt_reason = state.halt_reason;
+                result.inventory_utilization = state.inventory_utilization();
+                result.exposure_utilization = state.exposure_utilization();
+            }
+        }
+    }
+
+    /// Remove cross-outcome arbitrage against our quotes: buying every
+    /// outcome from us must cost at least 1 and selling every outcome to us
+    /// must pay at most 1. Shortfalls are spread evenly across the legs with
+    /// room left, so a leg pinned at 0 or 1 passes the rest of its share on,
+    /// and a side pinned there is pulled rather than posted at a price that
+    /// offers nothing. Quotes only ever widen. Groups with a member not
+    /// quoting are left alone, since a taker cannot complete the set.
+    fn reconcile_group_quotes(&self, quotes: &mut HashMap<String, Option<Quote>>) {
+        for members in self.outcome_groups.values() {
+            let legs: Option<Vec<Quote>> =
+                members.iter().map(|name| quotes.get(name).copied().flatten()).collect();
+            let Some(legs) = legs else { continue };
+            if legs.is_empty() {
+                continue;
+            }
+            
+            let mut asks: Vec<f64> = legs.iter().map(|q| q.ask).collect();
+            let mut bids: Vec<f64> = legs.iter().map(|q| q.bid).collect();
+            let asks_pinned = spread_to_total(&mut asks, 1.0, true);
+            let bids_pinned = spread_to_total(&mut bids, 1.0, false);
+            for (i, (name, mut quote)) in members.iter().zip(legs).enumerate() {
+                quote.ask = asks[i];
+                quote.bid = bids[i];
+                if asks_pinned[i] {
+                    quote.ask_size = 0.0;
+                }
+                if bids_pinned[i] {
+                    quote.bid_size = 0.0;
+                }
+                quotes.insert(name.clone(), Some(quote));
+            }
+        }
+    }
+
+    /// Settle a mutually-exclusive outcome group in one call: the winner
+    /// pays 1.0 and every other member 0.0
+    pub fn settle_group(&mut self, group: &str, winner: &str) -> Result<(), BotError> {
+        let members = self
+            .outcome_groups
+            .get(group)
+            .ok_or_else(|| BotError::UnknownGroup(group.to_string()))?
+            .clone();
+        if !members.iter().any(|name| name == winner) {
+            return Err(BotError::NotInGroup {
+                group: group.to_string(),
+                market: winner.to_string(),
+            });
+        }
+        for name in &members {
+            match self.markets.get(name) {
+                None => return Err(BotError::UnknownMarket(name.clone())),
+                Some(state) if state.resolved.is_some() => return Err(BotError::AlreadySettled(name.clone())),
+                Some(_) => {}
+            }
+        }
+        
+        for name in &members {
+            self.settle_market(name, name == winner)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve a market now: inventory pays out at 1.0 on YES and 0.0 on
+    /// NO, booked into realized PnL against its average cost, and the market
+    /// stops trading
+    pub fn resolve(&mut self, market: &str, outcome: bool) -> Result<(), BotError> {
+        if self.market(market)?.resolved.is_some() {
+            return Err(BotError::AlreadySettled(market.to_string()));
+        }
+        self.settle_market(market, outcome)
+    }
+
+    /// Settle one market and record it in the settlement ledger
+    fn settle_market(&mut self, name: &str, outcome: bool) -> Result<(), BotError> {
+        let state = self
+            .markets
+            .get_mut(name)
+            .ok_or_else(|| BotError::UnknownMarket(name.to_string()))?;
+        let inventory = state.inventory;
+        let realized_before = state.realized_pnl;
+        state.settle(outcome);
+        state.amend_pnl_sample();
+        
+        self.settlement_ledger.push(SettlementRecord {
+            market: name.to_string(),
+            step: self.time,
+            outcome,
+            inventory,
+            settlement_price: state.mid,
+            pre_settlement_realized_pnl: realized_before,
+            settlement_pnl: state.realized_pnl - realized_before,
+        });
+        Ok(())
+    }
+
+    /// Settle every market whose resolution step has been reached. Standalone
+    /// markets draw their outcome from their own RNG with P(YES) = mid; an
+    /// outcome group settles as a whole once any member is due, with the
+    /// winner drawn in proportion to the members' mids.
+    fn settle_due_markets(&mut self, results: &mut HashMap<String, StepResult>) {
+        let time = self.time;
+        let is_due = |state: &MarketState| {
+            state.resolved.is_none() && state.resolution_step.is_some_and(|step| step <= time)
+        };
+        
+        let mut due_groups: Vec<(String, Vec<String>)> = self
+            .outcome_groups
+            .iter()
+            .filter(|(_, members)| members.iter().any(|m| self.markets.get(m).is_some_and(is_due)))
+            .map(|(group, members)| (group.clone(), members.clone()))
+            .collect();
+        due_groups.sort();
+        for (group, members) in due_groups {
+            let settled = self
+                .draw_group_winner(&members)
+                .and_then(|winner| self.settle_group(&group, &winner));
+            if let Err(e) = settled {
+                eprintln!("⚠️  Could not settle outcome group {}: {}", group, e);
+            }
+        }
+        
+        let mut due: Vec<String> = self
+            .markets
+            .iter()
+            .filter(|(name, state)| {
+                is_due(state) && !self.outcome_groups.values().any(|members| members.contains(name))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        due.sort();
+        for name in due {
+            let Some(rng) = self.rngs.get_mut(&name) else {
+                eprintln!("⚠️  Could not settle {}: it has no order-flow RNG", name);
+                continue;
+            };
+            let outcome = rng.gen::<f64>() < self.markets[&name].mid;
+            if let Err(e) = self.settle_market(&name, outcome) {
+                eprintln!("⚠️  Could not settle {}: {}", name, e);
+            }
+        }
+        
+        // Markets that traded this step and are now settled resolved just now
+        for (name, result) in results.iter_mut() {
+            let state = &self.markets[name];
+            if state.resolved.is_some() {
+                result.mid = state.mid;
+                result.inventory = state.inventory;
+                result.pnl = state.pnl;
+                result.inventory_utilization = state.inventory_utilization();
+                result.exposure_utilization = state.exposure_utilization();
+            }
+        }
+    }
+
+    fn draw_group_winner(&mut self, members: &[String]) -> Result<String, BotError> {
+        let weights: Vec<f64> = members
+            .iter()
+            .map(|name| self.markets.get(name).map_or(0.0, |s| s.mid))
+            .collect();
+        let total: f64 = weights.iter().sum();
+        // Only groups with a due member are drawn, so there is a first member
+        let first = &members[0];
+        let rng = self.rngs.get_mut(first).ok_or_else(|| BotError::IncompleteMarket {
+            market: first.clone(),
+            missing: "order-flow RNG",
+        })?;
+        let mut draw = rng.gen::<f64>() * total;
+        
+        for (name, weight) in members.iter().zip(&weights) {
+            if draw < *weight {
+                return Ok(name.clone());
+            }
+            draw -= weight;
+        }
+        Ok(members[members.len() - 1].clone())
+    }
+
+    pub fn total_pnl(&self) -> f64 {
+        self.states_in_order().iter().map(|s| s.pnl).sum()
+    }
+
+    /// Markets sorted by name, so float sums over them come out the same on
+    /// every run
+    pub fn states_in_order(&self) -> Vec<&MarketState> {
+        let mut states: Vec<(&String, &MarketState)> = self.markets.iter().collect();
+        states.sort_by(|a, b| a.0.cmp(b.0));
+        states.into_iter().map(|(_, state)| state).collect()
+    }
+
+    /// Sharpe, Sortino and volatility of the summed per-step PnL changes;
+    /// markets that stopped early contribute nothing after they stop
+    pub fn portfolio_performance(&self) -> PerformanceReport {
+        let steps = self.markets.values().map(|s| s.pnl_history.len()).max().unwrap_or(0);
+        let mut series = vec![0.0; steps];
+        for state in self.states_in_order() {
+            for (total, change) in series.iter_mut().zip(&state.pnl_history) {
+                *total += change;
+            }
+        }
+        PerformanceReport::new(&series, self.risk_free * self.markets.len() as f64, self.steps_per_year)
+    }
+
+    /// Portfolio Calmar ratio over the steps run so far
+    pub fn calmar_ratio(&self) -> f64 {
+        let steps = self.time.saturating_sub(self.warmup_steps);
+        calmar_ratio(self.reported_pnl(), self.portfolio_max_drawdown, steps, self.steps_per_year)
+    }
+
+    /// Market snapshot including its maker's quote telemetry
+    pub fn snapshot(&self, market_name: &str) -> Option<MarketSnapshot> {
+        let state = self.markets.get(market_name)?;
+        let mut snapshot = state.snapshot();
+        snapshot.calmar_ratio = state.calmar_ratio(self.steps_per_year);
+        snapshot.performance = PerformanceReport::new(&state.pnl_history, self.risk_free, self.steps_per_year);
+        if let Some(mm) = self.market_makers.get(market_name) {
+            snapshot.quote_clamps = mm.clamps;
+        }
+        Some(snapshot)
+    }
+
+    /// True once every market has a resolution step and has been settled
+    pub fn all_settled(&self) -> bool {
+        !self.markets.is_empty() && self.markets.values().all(|s| s.resolved.is_some())
+    }
+
     /// Run simulation for a given number of steps
     pub fn run(&mut self, steps: usize) -> Vec<HashMap<String, StepResult>> {
+        self.run_with(steps, |_, _| {})
+    }
+
+    /// Run simulation like `run`, also timing every step to report
+    /// throughput and the slowest step
+    pub fn run_timed(&mut self, steps: usize) -> (Vec<HashMap<String, StepResult>>, RunStats) {
+        let start = Instant::now();
+        let mut last = start;
+        let mut slowest_step = Duration::ZERO;
+        let trace = self.run_with(steps, |_, _| {
+            let now = Instant::now();
+            slowest_step = slowest_step.max(now - last);
+            last = now;
+        });
+        let elapsed = start.elapsed();
+        let stats = RunStats {
+            total_steps: trace.len(),
+            elapsed,
+            steps_per_sec: trace.len() as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+            slowest_step,
+        };
+        (trace, stats)
+    }
+
+    /// Run simulation writing each step's results to `writer` as one JSON
+    /// line (JSONL) instead of collecting the trace; returns the steps run
+    pub fn run_streaming<W: Write>(&mut self, steps: usize, writer: W) -> io::Result<usize> {
+        let mut trace = TraceWriter::new(writer, 1);
+        self.run_streaming_with(steps, &mut trace, |_, _| {})
+    }
+
+    /// Like `run_streaming`, writing through `trace` (and its batching) and
+    /// calling `on_step` with the engine and each step's results
+    pub fn run_streaming_with<W, F>(&mut self, steps: usize, trace: &mut TraceWriter<W>, mut on_step: F) -> io::Result<usize>
+    where
+        W: Write,
+        F: FnMut(&ExecutionEngine, &HashMap<String, StepResult>),
+    {
+        let mut written = 0;
+        for _ in 0..steps {
+            if self.replay_exhausted() || interrupt::requested() {
+                break;
+            }
+            let results = self.step();
+            on_step(self, &results);
+            trace.write_step(&results)?;
+            written += 1;
+            self.checkpoint_if_due();
+            
+            // Nothing left to simulate once every market has resolved
+            if self.all_settled() {
+                break;
+            }
+        }
+        trace.flush()?;
+        Ok(written)
+    }
+
+    /// Run simulation, calling `on_step` with the engine and each step's results
+    pub fn run_with<F>(&mut self, steps: usize, mut on_step: F) -> Vec<HashMap<String, StepResult>>
+    where
+        F: FnMut(&ExecutionEngine, &HashMap<String, StepResult>),
+    {
         let mut trace = Vec::with_capacity(steps);
         
         for _ in 0..steps {
-            trace.push(self.step());
+            if self.replay_exhausted() || interrupt::requested() {
+                break;
+            }
+            let results = self.step();
+            on_step(self, &results);
+            trace.push(results);
+            self.checkpoint_if_due();
+            
+            // Nothing left to simulate once every market has resolved
+            if self.all_settled() {
+                break;
+            }
         }
         
         trace
     }
+
+    /// Write a checkpoint when the current step lands on the configured interval
+    pub fn checkpoint_if_due(&self) {
+        if let Some(checkpoint) = &self.checkpoint {
+            if checkpoint.interval > 0 && self.time.is_multiple_of(checkpoint.interval) {
+                // A failed checkpoint shouldn't abort the run it protects
+                if let Err(e) = self.write_checkpoint() {
+                    eprintln!("⚠️  Checkpoint at step {} failed: {}", self.time, e);
+                }
+            }
+        }
+    }
+
+    /// Write the full engine state to a timestamped file in the checkpoint dir
+    pub fn write_checkpoint(&self) -> Result<PathBuf, BotError> {
+        let dir = match &self.checkpoint {
+            Some(checkpoint) => checkpoint.dir.clone(),
+            None => return Err(BotError::CheckpointNotConfigured),
+        };
+        fs::create_dir_all(&dir)?;
+        
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+        let path = dir.join(format!("checkpoint_step{:06}_{}.json", self.time, stamp));
+        fs::write(&path, serde_json::to_string(self)?)?;
+        Ok(path)
+    }
+
+    /// Restore an engine from a checkpoint written by `write_checkpoint`
+    pub fn load_checkpoint(path: &str) -> Result<Self, BotError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markets() -> HashMap<String, MarketState> {
+        ["alpha", "beta", "gamma"]
+            .iter()
+            .map(|name| (name.to_string(), MarketState::new(name, 0.5)))
+            .collect()
+    }
+
+    /// Everything a run leaves behind, in a stable order. Fill timestamps
+    /// are included, so comparisons need step stamps rather than wall-clock
+    #[cfg(not(feature = "wall-clock"))]
+    fn final_state(engine: &ExecutionEngine) -> String {
+        serde_json::to_string(&(engine.states_in_order(), engine.rng_position(), engine.time)).unwrap()
+    }
+
+    #[cfg(not(feature = "wall-clock"))]
+    #[test]
+    fn resuming_from_a_checkpoint_matches_an_uninterrupted_run() {
+        let mut uninterrupted = ExecutionEngine::new(markets(), 211).unwrap();
+        uninterrupted.run(60);
+
+        let dir = std::env::temp_dir().join(format!("checkpoint_test_{}", std::process::id()));
+        let mut first_leg = ExecutionEngine::new(markets(), 211).unwrap();
+        first_leg.checkpoint = Some(CheckpointConfig {
+            interval: 25,
+            dir: dir.clone(),
+        });
+        first_leg.run(25);
+        let path = first_leg.write_checkpoint().unwrap();
+
+        let mut resumed = ExecutionEngine::load_checkpoint(path.to_str().unwrap()).unwrap();
+        resumed.checkpoint = None;
+        assert_eq!(resumed.time, 25);
+        resumed.run(35);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(final_state(&resumed), final_state(&uninterrupted));
+    }
+
+    #[test]
+    fn positive_drift_trends_the_mid_up_without_flow() {
+        let mids = |drift: f64| {
+            let mut state = MarketState::new("trending", 0.5);
+            state.drift = drift;
+            let mut engine = ExecutionEngine::new(HashMap::from([("trending".to_string(), state)]), 217).unwrap();
+            engine.set_orders_per_tick(0, 0).unwrap();
+            let trace = engine.run(100);
+            trace.iter().map(|step| step["trending"].mid).collect::<Vec<f64>>()
+        };
+
+        let flat = mids(0.0);
+        assert!(flat.iter().all(|mid| (mid - 0.5).abs() < 1e-12));
+
+        let trending = mids(0.001);
+        assert!(trending.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!(trending[99] > 0.55, "mid only reached {}", trending[99]);
+    }
+
+    #[test]
+    fn withdrawal_tightens_the_capital_fraction_limit_and_pulls_the_bid() {
+        let mut state = MarketState::new("funded", 0.5);
+        state.capital = 1000.0;
+        state.inventory_limit_mode = crate::market_state::InventoryLimit::CapitalFraction { fraction: 0.1 };
+        state.capital_events = vec![(5, -800.0)];
+        state.add_inventory_at_mid(150.0);
+        let mut engine = ExecutionEngine::new(HashMap::from([("funded".to_string(), state)]), 233).unwrap();
+        engine.set_orders_per_tick(0, 0).unwrap();
+        engine.explain_quotes = true;
+
+        let trace = engine.run(10);
+        for (step, results) in trace.iter().enumerate() {
+            let result = &results["funded"];
+            let rationale = result.rationale.as_ref().expect("explained quote");
+            // 0.1 * 1000 / 0.5 = 200 allows the 150 held; after the
+            // withdrawal 0.1 * 200 / 0.5 = 40 does not
+            assert_eq!(rationale.bid_withheld, step >= 5, "step {}", step);
+            let expected_utilization = if step >= 5 { 150.0 / 40.0 } else { 150.0 / 200.0 } * 100.0;
+            let utilization = result.inventory_utilization.unwrap();
+            assert!((utilization - expected_utilization).abs() < 1e-9, "step {}: {}", step, utilization);
+        }
+    }
+
+    #[test]
+    fn construction_rejects_or_skips_non_finite_markets() {
+        let mut broken = markets();
+        broken.get_mut("beta").unwrap().mid = f64::NAN;
+        broken.get_mut("gamma").unwrap().inventory_limit = f64::INFINITY;
+
+        match ExecutionEngine::new(broken.clone(), 239) {
+            Err(BotError::NonFiniteMarkets(bad)) => assert_eq!(
+                bad,
+                vec![("beta".to_string(), vec!["mid"]), ("gamma".to_string(), vec!["inventory_limit"])]
+            ),
+            other => panic!("expected NonFiniteMarkets, got {:?}", other.map(|engine| engine.time)),
+        }
+
+        let engine = ExecutionEngine::with_policy(broken, 239, 239, NonFinitePolicy::Skip).unwrap();
+        assert_eq!(engine.markets.keys().collect::<Vec<_>>(), vec!["alpha"]);
+        assert!(engine.market_makers.contains_key("alpha") && engine.market_makers.len() == 1);
+    }
+
+    #[cfg(not(feature = "wall-clock"))]
+    #[test]
+    fn rng_position_fast_forwards_a_rewound_engine() {
+        let mut original = ExecutionEngine::new(markets(), 250).unwrap();
+        original.run(30);
+        let at_step_30 = original.rng_position();
+        let copy = serde_json::to_string(&original).unwrap();
+        let expected = serde_json::to_value(original.run(40)).unwrap();
+
+        // The same state with every stream rewound to the start diverges...
+        let start = ExecutionEngine::new(markets(), 250).unwrap().rng_position();
+        let mut rewound: ExecutionEngine = serde_json::from_str(&copy).unwrap();
+        rewound.set_rng_position(&start).unwrap();
+        assert_ne!(serde_json::to_value(rewound.run(40)).unwrap(), expected);
+
+        // ...until it is fast-forwarded to the captured position
+        let mut restored: ExecutionEngine = serde_json::from_str(&copy).unwrap();
+        restored.set_rng_position(&start).unwrap();
+        restored.set_rng_position(&at_step_30).unwrap();
+        assert_eq!(serde_json::to_value(restored.run(40)).unwrap(), expected);
+        assert_eq!(final_state(&restored), final_state(&original));
+    }
+
+    #[cfg(not(feature = "wall-clock"))]
+    #[test]
+    fn loss_cap_freezes_only_the_breaching_market() {
+        let mut capped = markets();
+        let alpha = capped.get_mut("alpha").unwrap();
+        alpha.realized_pnl = -10.0; // a session that already lost 10
+        alpha.session_loss_cap = Some(5.0);
+        let mut engine = ExecutionEngine::new(capped, 251).unwrap();
+        let mut control = ExecutionEngine::new(markets(), 251).unwrap();
+
+        let first = engine.step();
+        control.step();
+        assert!(first["alpha"].frozen);
+        let alpha_fills = engine.markets["alpha"].fill_count;
+
+        let trace = engine.run(60);
+        control.run(60);
+        assert!(trace.iter().all(|step| step["alpha"].frozen && !step["beta"].frozen && !step["gamma"].frozen));
+        assert_eq!(engine.markets["alpha"].fill_count, alpha_fills);
+        assert_eq!(engine.markets["alpha"].halt_reason, Some(HaltReason::SessionCap));
+        for name in ["beta", "gamma"] {
+            assert!(engine.markets[name].fill_count > 0);
+            assert_eq!(
+                serde_json::to_string(&engine.markets[name]).unwrap(),
+                serde_json::to_string(&control.markets[name]).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn resolving_true_pays_long_inventory_out_at_one() {
+        let mut engine = ExecutionEngine::new(markets(), 259).unwrap();
+        let state = engine.markets.get_mut("alpha").unwrap();
+        state.record_fill("buy", 100.0, 0.4, 0.5, 0);
+        state.mark_to_market();
+        let realized_before = state.realized_pnl;
+
+        engine.resolve("alpha", true).unwrap();
+        let state = &engine.markets["alpha"];
+        assert_eq!(state.resolved, Some(true));
+        assert_eq!(state.inventory, 0.0);
+        assert!((state.realized_pnl - realized_before - 100.0 * (1.0 - 0.4)).abs() < 1e-9);
+        assert!((state.pnl - 60.0).abs() < 1e-9);
+
+        // Settled markets sit out later steps
+        let settled = serde_json::to_string(state).unwrap();
+        engine.run(20);
+        assert_eq!(serde_json::to_string(&engine.markets["alpha"]).unwrap(), settled);
+        assert!(engine.resolve("alpha", false).is_err());
+    }
+
+    #[test]
+    fn run_streaming_writes_one_json_line_per_step() {
+        let mut engine = ExecutionEngine::new(markets(), 265).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(engine.run_streaming(1000, &mut out).unwrap(), 1000);
+
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 1000);
+        for line in lines {
+            let step: BTreeMap<String, serde_json::Value> = serde_json::from_str(line).unwrap();
+            assert_eq!(step.keys().collect::<Vec<_>>(), vec!["alpha", "beta", "gamma"]);
+        }
+    }
+
+    #[cfg(not(feature = "wall-clock"))]
+    #[test]
+    fn identical_seeded_runs_write_byte_identical_traces() {
+        let trace_json = || {
+            let trace = ExecutionEngine::new(markets(), 268).unwrap().run(200);
+            let ordered: Vec<_> = trace.iter().map(by_market).collect();
+            serde_json::to_string_pretty(&ordered).unwrap()
+        };
+        let first = trace_json();
+        assert!(first.contains("\"fills\""));
+        assert_eq!(first.as_bytes(), trace_json().as_bytes());
+    }
+
+    #[test]
+    fn fixed_orders_per_tick_generates_exactly_that_many() {
+        let mut engine = ExecutionEngine::new(markets(), 282).unwrap();
+        assert_eq!(
+            engine.set_orders_per_tick(6, 5),
+            Err(BotError::InvalidOrdersPerTick { min: 6, max: 5 })
+        );
+        engine.set_orders_per_tick(5, 5).unwrap();
+
+        let mut orders = Vec::new();
+        for _ in 0..50 {
+            for name in ["alpha", "beta", "gamma"] {
+                engine.simulate_order_flow(name, &mut orders).unwrap();
+                assert_eq!(orders.len(), 5);
+            }
+            engine.step();
+        }
+    }
+
+    #[test]
+    fn warmup_hides_a_drawdown_forced_inside_it() {
+        let forced_loss = |warmup_steps| {
+            let mut engine = ExecutionEngine::new(markets(), 285).unwrap();
+            engine.warmup_steps = warmup_steps;
+            engine.run(5);
+            engine.markets.get_mut("alpha").unwrap().cash -= 50.0;
+            engine.run(45);
+            engine
+        };
+
+        let cold = forced_loss(0);
+        assert!(cold.markets["alpha"].max_drawdown >= 50.0);
+
+        let warm = forced_loss(10);
+        let alpha = &warm.markets["alpha"];
+        assert!(alpha.max_drawdown < 50.0);
+        assert!(warm.portfolio_max_drawdown < 50.0);
+        assert_eq!(alpha.reported_steps(), 40);
+        let expected = calmar_ratio(alpha.reported_pnl(), alpha.max_drawdown, 40, warm.steps_per_year);
+        assert_eq!(alpha.calmar_ratio(warm.steps_per_year), expected);
+    }
+
+    #[test]
+    fn runs_stop_once_every_market_has_settled() {
+        let expiring = || {
+            let mut markets = markets();
+            for state in markets.values_mut() {
+                state.resolution_step = Some(50);
+            }
+            ExecutionEngine::new(markets, 216).unwrap()
+        };
+
+        let mut engine = expiring();
+        let trace = engine.run(200);
+        assert!(engine.all_settled());
+        assert_eq!(trace.len(), 50);
+        assert_eq!(engine.time, 50);
+
+        let mut streamed = expiring();
+        assert_eq!(streamed.run_streaming(200, io::sink()).unwrap(), 50);
+    }
+
+    #[test]
+    fn settling_a_group_pays_out_exactly_once() {
+        let mut engine = ExecutionEngine::new(markets(), 222).unwrap();
+        engine.outcome_groups.insert("election".to_string(), vec!["alpha".to_string(), "beta".to_string(), "gamma".to_string()]);
+        for state in engine.markets.values_mut() {
+            state.record_fill("buy", 10.0, 0.3, 0.5, 0);
+            state.mark_to_market();
+        }
+        let cash_before: f64 = engine.markets.values().map(|s| s.cash).sum();
+
+        engine.settle_group("election", "beta").unwrap();
+        let cash_after: f64 = engine.markets.values().map(|s| s.cash).sum();
+        let group_pnl: f64 = engine.markets.values().map(|s| s.pnl).sum();
+        // 30 bought at 0.3 across the group; only beta's 10 pay out at 1.0
+        assert!((cash_after - cash_before - 10.0).abs() < 1e-9);
+        assert!((group_pnl - (10.0 - 30.0 * 0.3)).abs() < 1e-9, "group pnl {}", group_pnl);
+        assert_eq!(engine.settlement_ledger.iter().filter(|r| r.outcome).count(), 1);
+        assert_eq!(
+            engine.settle_group("election", "beta"),
+            Err(BotError::AlreadySettled("alpha".to_string()))
+        );
+    }
+
+    #[test]
+    fn flow_seed_leaves_the_unperturbed_price_path_alone() {
+        let mids = |flow_seed: u64, orders: u32| {
+            let mut engine = ExecutionEngine::with_seeds(markets(), flow_seed, 230).unwrap();
+            engine.set_correlation(&HashMap::from([("alpha".to_string(), 1.0), ("beta".to_string(), 0.5)])).unwrap();
+            engine.set_orders_per_tick(orders, orders).unwrap();
+            let trace = engine.run(100);
+            trace.iter().map(|step| (step["alpha"].mid, step["beta"].mid)).collect::<Vec<_>>()
+        };
+
+        let path = mids(1, 0);
+        assert!(path.iter().any(|(alpha, _)| (alpha - 0.5).abs() > 1e-6), "the factor moves the mid");
+        assert_eq!(mids(2, 0), path);
+        // With flow on, the flow seed does steer the mid through fills
+        assert_ne!(mids(1, 3), mids(2, 3));
+    }
+
+    #[test]
+    fn step_stamps_follow_emission_order() {
+        let trace = ExecutionEngine::new(markets(), 236).unwrap().run(80);
+        for (index, results) in trace.iter().enumerate() {
+            assert!(results.values().all(|result| result.step == index as u64), "step {}", index);
+        }
+
+        let mut engine = ExecutionEngine::new(markets(), 236).unwrap();
+        engine.run(20);
+        let mut out = Vec::new();
+        engine.run_streaming(30, &mut out).unwrap();
+        for (index, line) in std::str::from_utf8(&out).unwrap().lines().enumerate() {
+            let step: HashMap<String, StepResult> = serde_json::from_str(line).unwrap();
+            assert!(step.values().all(|result| result.step == 20 + index as u64), "line {}", index);
+        }
+    }
+
+    #[test]
+    fn exposure_follows_a_drifting_mid_without_fills() {
+        let mut state = MarketState::new("drifting", 0.5);
+        state.drift = 0.002;
+        state.add_inventory_at_mid(100.0);
+        let mut engine = ExecutionEngine::new(HashMap::from([("drifting".to_string(), state)]), 242).unwrap();
+        engine.set_orders_per_tick(0, 0).unwrap();
+
+        let mut exposures = Vec::new();
+        engine.run_with(50, |engine, results| {
+            let state = &engine.markets["drifting"];
+            assert!(results["drifting"].fills.is_empty());
+            assert!((state.exposure - 100.0 * state.mid).abs() < 1e-9, "step {}", results["drifting"].step);
+            exposures.push(state.exposure);
+        });
+        assert!(exposures.windows(2).all(|pair| pair[1] > pair[0]));
+    }
+
+    #[test]
+    fn audited_config_records_the_adaptive_values_of_each_step() {
+        let mut state = MarketState::new("audited", 0.5);
+        state.capital = 1000.0;
+        state.inventory_limit_mode = crate::market_state::InventoryLimit::CapitalFraction { fraction: 0.1 };
+        state.capital_events = vec![(10, -500.0)];
+        let mut engine = ExecutionEngine::new(HashMap::from([("audited".to_string(), state)]), 245).unwrap();
+        engine.audit_config = true;
+
+        let trace = engine.run(30);
+        let audits: Vec<&EffectiveConfig> = trace
+            .iter()
+            .map(|results| {
+                assert!(results["audited"].rationale.is_none(), "auditing alone attaches no rationale");
+                results["audited"].config.as_ref().expect("audited step")
+            })
+            .collect();
+        let spreads: Vec<f64> = audits.iter().map(|audit| audit.spread.unwrap()).collect();
+        assert!(spreads.windows(2).any(|pair| pair[0] != pair[1]), "spread never adapted: {:?}", spreads);
+        assert!(audits.windows(2).any(|pair| pair[0].skew != pair[1].skew));
+        // Halving the capital halves the capital-fraction limit
+        let (before, after) = (audits[9].inventory_limit.unwrap(), audits[10].inventory_limit.unwrap());
+        assert!(after < 0.6 * before, "limit {} -> {}", before, after);
+        assert!(audits.iter().all(|audit| audit.config.base_spread == audits[0].config.base_spread));
+    }
+
+    #[test]
+    fn reconciled_groups_offer_no_arbitrage_even_when_a_leg_pins() {
+        let mut engine = ExecutionEngine::new(markets(), 248).unwrap();
+        let names = ["alpha", "beta", "gamma"].map(String::from);
+        engine.outcome_groups.insert("race".to_string(), names.to_vec());
+        let quote = |mid: f64| Quote { bid: mid - 0.02, ask: mid + 0.02, bid_size: 5.0, ask_size: 5.0 };
+        let original = [quote(0.9), quote(0.9), quote(0.05)];
+        let mut quotes: HashMap<String, Option<Quote>> =
+            names.iter().cloned().zip(original.iter().copied().map(Some)).collect();
+
+        engine.reconcile_group_quotes(&mut quotes);
+        let legs: Vec<Quote> = names.iter().map(|name| quotes[name].unwrap()).collect();
+        for (leg, before) in legs.iter().zip(&original) {
+            assert!(leg.bid <= before.bid && leg.ask >= before.ask, "{:?} narrowed from {:?}", leg, before);
+            assert!(leg.bid >= 0.0 && leg.ask <= 1.0);
+        }
+        // gamma's bid pins at 0 and is pulled; the others absorb its share
+        assert_eq!((legs[2].bid, legs[2].bid_size), (0.0, 0.0));
+        assert!(legs[0].bid_size > 0.0 && legs[1].bid_size > 0.0);
+        let posted_bids: f64 = legs.iter().filter(|q| q.bid_size > 0.0).map(|q| q.bid).sum();
+        assert!(posted_bids <= 1.0 + 1e-9, "selling the set to us pays {}", posted_bids);
+        assert!(legs.iter().zip(&original).all(|(leg, before)| leg.ask == before.ask), "asks already cost over 1");
+
+        // Low mids push every ask up until the set costs 1
+        let original = [quote(0.1), quote(0.05), quote(0.04)];
+        let mut quotes: HashMap<String, Option<Quote>> =
+            names.iter().cloned().zip(original.iter().copied().map(Some)).collect();
+        engine.reconcile_group_quotes(&mut quotes);
+        let asks: f64 = names.iter().map(|name| quotes[name].unwrap().ask).sum();
+        assert!(asks >= 1.0 - 1e-9, "buying the set from us costs {}", asks);
+    }
+
+    #[test]
+    fn settlement_ledger_adds_up_to_the_reported_total() {
+        let mut markets = markets();
+        for state in markets.values_mut() {
+            state.resolution_step = Some(80);
+        }
+        let mut engine = ExecutionEngine::new(markets, 254).unwrap();
+        engine.run(200);
+        assert!(engine.all_settled());
+        assert_eq!(engine.settlement_ledger.len(), 3);
+        assert!(engine.settlement_ledger.iter().any(|record| record.inventory != 0.0));
+
+        for record in &engine.settlement_ledger {
+            let state = &engine.markets[&record.market];
+            let total = record.pre_settlement_realized_pnl + record.settlement_pnl;
+            assert!((total - state.reported_pnl()).abs() < 1e-9, "{}: {} vs {}", record.market, total, state.reported_pnl());
+        }
+        let ledger_total: f64 = engine
+            .settlement_ledger
+            .iter()
+            .map(|record| record.pre_settlement_realized_pnl + record.settlement_pnl)
+            .sum();
+        assert!((ledger_total - engine.reported_pnl()).abs() < 1e-9);
+    }
+
+    /// Buys `size` every tick, whatever the market does
+    struct SteadyBuyer {
+        size: f64,
+    }
+
+    impl OrderFlow for SteadyBuyer {
+        fn generate(&mut self, _: &MarketState, _: &mut ChaCha8Rng, _: (u32, u32), orders: &mut Vec<Order>) {
+            orders.clear();
+            orders.push(Order { side: "buy".to_string(), size: self.size, price: 1.0 });
+        }
+
+        fn clone_box(&self) -> Box<dyn OrderFlow> {
+            Box::new(SteadyBuyer { size: self.size })
+        }
+    }
+
+    #[test]
+    fn a_plugged_in_flow_drives_its_market_only() {
+        let mut engine = ExecutionEngine::new(markets(), 257).unwrap();
+        engine.flow_models.insert("alpha".to_string(), Box::new(SteadyBuyer { size: 1.0 }));
+        engine.flow_models.insert("beta".to_string(), Box::new(OrderFlowModel::Adversarial));
+        let trace = engine.run(20);
+
+        assert!(trace.iter().all(|step| {
+            step["alpha"].fills.len() == 1 && step["alpha"].fills[0].side == "sell" && step["alpha"].fills[0].size == 1.0
+        }));
+        assert_eq!(engine.markets["alpha"].inventory, -20.0);
+        assert!(serde_json::to_string(&engine).is_err(), "custom flow cannot be checkpointed");
+
+        engine.flow_models.remove("alpha");
+        let restored: ExecutionEngine = serde_json::from_str(&serde_json::to_string(&engine).unwrap()).unwrap();
+        assert_eq!(restored.flow_models["beta"].builtin(), Some(OrderFlowModel::Adversarial));
+        assert_eq!(restored.flow_model.builtin(), Some(OrderFlowModel::MidBiased));
+    }
+
+    #[test]
+    fn mids_revert_toward_their_anchor() {
+        let mut markets = HashMap::new();
+        for (name, mid) in [("below", 0.3), ("above", 0.95)] {
+            let mut state = MarketState::new(name, mid);
+            state.reversion_anchor = 0.7;
+            state.reversion_rate = 0.05;
+            markets.insert(name.to_string(), state);
+        }
+        let mut engine = ExecutionEngine::new(markets, 260).unwrap();
+        engine.set_orders_per_tick(0, 0).unwrap();
+
+        let trace = engine.run(200);
+        for (name, start) in [("below", 0.3_f64), ("above", 0.95)] {
+            let gaps: Vec<f64> = trace.iter().map(|step| (step[name].mid - 0.7).abs()).collect();
+            assert!(gaps.windows(2).all(|pair| pair[1] < pair[0]), "{} moved away from its anchor", name);
+            assert!(gaps[199] < 0.01 * (start - 0.7).abs(), "{} only reached {}", name, trace[199][name].mid);
+        }
+    }
+
+    #[test]
+    fn losses_past_capital_flatten_and_stop_the_market() {
+        let mut engine = ExecutionEngine::new(markets(), 262).unwrap();
+        let alpha = engine.markets.get_mut("alpha").unwrap();
+        alpha.capital = 50.0;
+        alpha.record_fill("buy", 40.0, 0.5, 0.5, 0);
+        engine.run(5);
+        // A loss bigger than the capital behind the market
+        engine.markets.get_mut("alpha").unwrap().cash -= 80.0;
+
+        let results = engine.step();
+        let alpha = &engine.markets["alpha"];
+        assert!(alpha.available_capital() < 0.0);
+        assert_eq!(alpha.halt_reason, Some(HaltReason::CapitalExhausted));
+        assert!(results["alpha"].frozen);
+        assert_eq!(alpha.inventory, 0.0);
+        assert!(results["alpha"].fills.last().is_some_and(|fill| fill.price == alpha.mid), "closed at the mid");
+
+        let fills = alpha.fill_count;
+        let trace = engine.run(30);
+        assert!(trace.iter().all(|step| step["alpha"].frozen && step["alpha"].fills.is_empty()));
+        assert_eq!(engine.markets["alpha"].fill_count, fills);
+        assert_eq!(engine.markets["alpha"].inventory, 0.0);
+        assert!(trace.iter().any(|step| !step["beta"].fills.is_empty()), "other markets keep trading");
+    }
+
+    #[test]
+    fn settlement_and_checkpoint_failures_are_typed() {
+        let mut engine = ExecutionEngine::new(markets(), 262).unwrap();
+        engine.outcome_groups.insert("election".to_string(), vec!["alpha".to_string(), "beta".to_string()]);
+
+        assert_eq!(
+            engine.settle_group("senate", "alpha"),
+            Err(BotError::UnknownGroup("senate".to_string()))
+        );
+        assert_eq!(
+            engine.settle_group("election", "gamma"),
+            Err(BotError::NotInGroup {
+                group: "election".to_string(),
+                market: "gamma".to_string(),
+            })
+        );
+        engine.resolve("gamma", true).unwrap();
+        assert_eq!(engine.resolve("gamma", false), Err(BotError::AlreadySettled("gamma".to_string())));
+        assert_eq!(engine.write_checkpoint(), Err(BotError::CheckpointNotConfigured));
+
+        let mut position = engine.rng_position();
+        position.flow.remove("beta");
+        assert_eq!(
+            engine.set_rng_position(&position),
+            Err(BotError::MissingRngPosition("beta".to_string()))
+        );
+    }
+
+    #[test]
+    fn positive_betas_move_together_on_a_factor_shock() {
+        let mut still = markets();
+        for state in still.values_mut() {
+            state.reversion_rate = 0.0;
+        }
+        let mut engine = ExecutionEngine::new(still, 271).unwrap();
+        engine.set_orders_per_tick(0, 0).unwrap();
+        engine.set_correlation(&HashMap::from([("alpha".to_string(), 1.0), ("beta".to_string(), 0.5)])).unwrap();
+
+        let trace = engine.run(50);
+        let mut previous = (0.5, 0.5);
+        for step in &trace {
+            let (alpha, beta) = (step["alpha"].mid - previous.0, step["beta"].mid - previous.1);
+            assert!(alpha != 0.0 && alpha.signum() == beta.signum(), "alpha moved {}, beta {}", alpha, beta);
+            assert!((beta - 0.5 * alpha).abs() < 1e-12, "beta moves half as far");
+            assert_eq!(step["gamma"].mid, 0.5, "an unloaded market ignores the factor");
+            previous = (step["alpha"].mid, step["beta"].mid);
+        }
+    }
+
+    /// A taker buy and a taker sell of 3 every tick
+    struct BothSides;
+
+    impl OrderFlow for BothSides {
+        fn generate(&mut self, _: &MarketState, _: &mut ChaCha8Rng, _: (u32, u32), orders: &mut Vec<Order>) {
+            orders.clear();
+            orders.push(Order { side: "buy".to_string(), size: 3.0, price: 1.0 });
+            orders.push(Order { side: "sell".to_string(), size: 3.0, price: 0.0 });
+        }
+
+        fn clone_box(&self) -> Box<dyn OrderFlow> {
+            Box::new(BothSides)
+        }
+    }
+
+    #[test]
+    fn a_drawdown_breach_halts_and_only_unwinds() {
+        let mut losing = markets();
+        let alpha = losing.get_mut("alpha").unwrap();
+        alpha.max_drawdown_limit = Some(1.0);
+        alpha.add_inventory_at_mid(50.0);
+        alpha.mark_to_market();
+        // A 0.2 drop on 50 long is a drawdown of 10, far past the limit
+        alpha.mid = 0.3;
+        alpha.mark_to_market();
+        let mut engine = ExecutionEngine::new(losing, 272).unwrap();
+        engine.flow_models.insert("alpha".to_string(), Box::new(BothSides));
+
+        // The breach is checked once the step's fills are marked, so the
+        // first step still quotes both sides
+        let first = engine.step();
+        assert_eq!(first["alpha"].halt_reason, Some(HaltReason::Drawdown));
+        let mut inventory = engine.markets["alpha"].inventory;
+        for _ in 0..40 {
+            let results = engine.step();
+            let alpha = &results["alpha"];
+            assert!(alpha.halted);
+            assert_eq!(alpha.halt_reason, Some(HaltReason::Drawdown));
+            assert!(alpha.fills.iter().all(|fill| fill.side == "sell"), "fills {:?}", alpha.fills);
+            let now = engine.markets["alpha"].inventory;
+            assert!((0.0..=inventory).contains(&now), "inventory went from {} to {}", inventory, now);
+            inventory = now;
+        }
+        assert_eq!(inventory, 0.0, "the halted market unwound until flat");
+    }
+
+    #[test]
+    fn fills_just_before_mid_jumps_score_as_toxic() {
+        let toxicity = |drift: f64| {
+            let mut trending = markets();
+            for state in trending.values_mut() {
+                state.drift = drift;
+            }
+            let mut engine = ExecutionEngine::new(trending, 277).unwrap();
+            engine.flow_models.insert("alpha".to_string(), Box::new(SteadyBuyer { size: 1.0 }));
+            engine.run(60);
+            engine.snapshot("alpha").expect("alpha is live").toxicity_score
+        };
+
+        // Every fill sells to a buyer just before the mid steps up
+        let toxic = toxicity(0.005);
+        assert!(toxic > 0.9, "toxicity {}", toxic);
+        // The same fills ahead of a falling mid are benign
+        let benign = toxicity(-0.005);
+        assert!(benign < 0.1, "toxicity {}", benign);
+    }
 }
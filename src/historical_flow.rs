@@ -0,0 +1,136 @@
+use crate::market_maker::Order;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Debug, Deserialize)]
+struct FlowRow {
+    timestamp: f64,
+    market: String,
+    side: String,
+    size: f64,
+    price: f64,
+}
+
+/// One recorded tick: every taker order that shared a timestamp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalTick {
+    pub timestamp: f64,
+    pub orders: HashMap<String, Vec<Order>>,
+}
+
+/// Recorded taker flow replayed in place of the simulated flow. Distinct
+/// timestamps become ticks in ascending order, and engine step N replays
+/// tick N.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalFlow {
+    pub ticks: Vec<HistoricalTick>,
+}
+
+impl HistoricalFlow {
+    pub fn from_csv_path(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Parse `timestamp,market,side,size,price` rows (with a header); rows
+    /// need not be sorted, but rows sharing a timestamp keep their order
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut rows = Vec::new();
+        for (line, row) in csv::Reader::from_reader(reader).deserialize().enumerate() {
+            let row: FlowRow = row?;
+            // + 2: one for the header, one for counting from 1
+            if row.side != "buy" && row.side != "sell" {
+                return Err(format!("row {}: side must be buy or sell, got {}", line + 2, row.side).into());
+            }
+            if !row.timestamp.is_finite() || !row.size.is_finite() || row.size <= 0.0 {
+                return Err(format!("row {}: timestamp must be finite and size positive", line + 2).into());
+            }
+            if !(0.0..=1.0).contains(&row.price) {
+                return Err(format!("row {}: price {} is outside [0, 1]", line + 2, row.price).into());
+            }
+            rows.push(row);
+        }
+        rows.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+
+        let mut ticks: Vec<HistoricalTick> = Vec::new();
+        for row in rows {
+            if ticks.last().is_none_or(|tick| tick.timestamp != row.timestamp) {
+                ticks.push(HistoricalTick {
+                    timestamp: row.timestamp,
+                    orders: HashMap::new(),
+                });
+            }
+            if let Some(tick) = ticks.last_mut() {
+                tick.orders.entry(row.market).or_default().push(Order {
+                    side: row.side,
+                    size: row.size,
+                    price: row.price,
+                });
+            }
+        }
+        Ok(HistoricalFlow { ticks })
+    }
+
+    /// Fill `orders` with `market`'s orders at `step`, cleared first like
+    /// `OrderFlowModel::generate`; a market silent in that tick gets none
+    pub fn orders_at(&self, step: u64, market: &str, orders: &mut Vec<Order>) {
+        orders.clear();
+        let tick = usize::try_from(step).ok().and_then(|step| self.ticks.get(step));
+        if let Some(market_orders) = tick.and_then(|tick| tick.orders.get(market)) {
+            orders.extend(market_orders.iter().cloned());
+        }
+    }
+
+    /// True once `step` is past the last recorded tick
+    pub fn exhausted(&self, step: u64) -> bool {
+        usize::try_from(step).map_or(true, |step| step >= self.ticks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution_engine::ExecutionEngine;
+    use crate::market_state::MarketState;
+
+    const RECORDING: &str = "timestamp,market,side,size,price
+2.0,alpha,sell,3,0.0
+1.0,alpha,buy,2,1.0
+1.0,beta,sell,1,0.0
+1.0,alpha,sell,4,0.0
+";
+
+    #[test]
+    fn rows_group_into_sorted_ticks_and_replay_to_the_end() {
+        let flow = HistoricalFlow::from_reader(RECORDING.as_bytes()).unwrap();
+        assert_eq!(flow.ticks.iter().map(|tick| tick.timestamp).collect::<Vec<_>>(), vec![1.0, 2.0]);
+
+        let mut orders = Vec::new();
+        flow.orders_at(0, "alpha", &mut orders);
+        let sides: Vec<(&str, f64)> = orders.iter().map(|o| (o.side.as_str(), o.size)).collect();
+        assert_eq!(sides, vec![("buy", 2.0), ("sell", 4.0)]);
+        flow.orders_at(1, "beta", &mut orders);
+        assert!(orders.is_empty());
+        assert!(!flow.exhausted(1) && flow.exhausted(2));
+
+        let markets = ["alpha", "beta"]
+            .iter()
+            .map(|name| (name.to_string(), MarketState::new(name, 0.5)))
+            .collect();
+        let mut engine = ExecutionEngine::new(markets, 270).unwrap();
+        engine.historical_flow = Some(flow);
+        let trace = engine.run(10);
+        assert_eq!(trace.len(), 2, "the run stops when the recording does");
+        let volume = |step: usize, market: &str| trace[step][market].fills.iter().map(|f| f.size).sum::<f64>();
+        assert_eq!((volume(0, "alpha"), volume(0, "beta"), volume(1, "alpha")), (6.0, 1.0, 3.0));
+    }
+
+    #[test]
+    fn a_row_with_an_unknown_side_is_rejected() {
+        let csv = "timestamp,market,side,size,price\n1.0,alpha,hold,1,0.5\n";
+        let error = HistoricalFlow::from_reader(csv.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "row 2: side must be buy or sell, got hold");
+    }
+}
@@ -0,0 +1,43 @@
+//! Ctrl-C as a stop request: the first SIGINT (or SIGTERM) sets a flag the
+//! run loops check between steps, so a run stops cleanly and streamed traces
+//! are flushed; a second signal kills the process as usual
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Route SIGINT and SIGTERM to the stop flag. Until this is called the
+/// signals keep their default behaviour, so embedders opt in
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn handle(signal: libc::c_int) {
+        REQUESTED.store(true, Ordering::SeqCst);
+        // Only storing and resetting are async-signal-safe here
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+    let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// No signal handling off unix; `request` still works
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Ask running loops to stop after their current step
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Clear a handled stop request
+pub fn reset() {
+    REQUESTED.store(false, Ordering::SeqCst);
+}
@@ -0,0 +1,28 @@
+//! Market-making simulation for binary event contracts, usable as a library
+//! so the engine can be embedded in other programs and integration tests.
+
+pub mod config;
+pub mod diffusion;
+pub mod error;
+pub mod execution_engine;
+pub mod historical_flow;
+pub mod interrupt;
+pub mod logger;
+pub mod market_maker;
+pub mod market_state;
+pub mod metrics;
+pub mod monte_carlo;
+pub mod order_flow;
+pub mod price_feed;
+pub mod shared_engine;
+
+pub use diffusion::CovarianceModel;
+pub use error::BotError;
+pub use execution_engine::{ExecutionEngine, FillInfo, RngPosition, RunStats, SettlementRecord, StepResult};
+pub use historical_flow::HistoricalFlow;
+pub use market_maker::{BookLevel, FillResult, MarketMaker, MarketMakerConfig, Order, OrderBook, Quote};
+pub use market_state::{Fill, MarketSeries, MarketSnapshot, MarketState};
+pub use metrics::PerformanceReport;
+pub use order_flow::{OrderFlow, OrderFlowModel};
+pub use price_feed::{FileFeed, HttpFeed, PolledFeed, PriceFeed, PriceFeedSource};
+pub use shared_engine::SharedEngine;
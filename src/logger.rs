@@ -1,8 +1,158 @@
-use crate::market_state::MarketState;
+use crate::execution_engine::{by_market, ExecutionEngine, RngPosition, SettlementRecord, StepResult};
+use crate::market_state::{MarketSnapshot, MarketState};
+use crate::metrics::PerformanceReport;
+use crate::monte_carlo::{EquityBand, McRun};
 use csv::Writer;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
 use std::fs::File;
+use std::io::{self, Write};
+
+/// Units used when printing prices and spreads. Internal math is always
+/// in probability units; conversion only happens on output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceDisplay {
+    Probability,
+    Cents,
+}
+
+impl PriceDisplay {
+    pub fn scale(self, value: f64) -> f64 {
+        match self {
+            PriceDisplay::Probability => value,
+            PriceDisplay::Cents => value * 100.0,
+        }
+    }
+
+    /// Convert the price-denominated fields of a snapshot for display
+    pub fn snapshot(self, snapshot: MarketSnapshot) -> MarketSnapshot {
+        MarketSnapshot {
+            mid: self.scale(snapshot.mid),
+            spread: self.scale(snapshot.spread),
+            cost_basis: snapshot.cost_basis.map(|basis| self.scale(basis)),
+            avg_quoted_spread: self.scale(snapshot.avg_quoted_spread),
+            breakeven_spread: self.scale(snapshot.breakeven_spread),
+            ..snapshot
+        }
+    }
+
+    /// Convert a step result's mid, spread, fill prices and quote rationale
+    /// for display; the audited maker config keeps its configured units
+    pub fn step_result(self, result: &StepResult) -> StepResult {
+        let mut result = result.clone();
+        result.mid = self.scale(result.mid);
+        result.spread = self.scale(result.spread);
+        for fill in result.fills.iter_mut() {
+            fill.price = self.scale(fill.price);
+        }
+        if let Some(why) = result.rationale.as_mut() {
+            for value in [
+                &mut why.model_spread,
+                &mut why.imbalance_widening,
+                &mut why.inventory_widening,
+                &mut why.toxic_widening,
+                &mut why.settlement_widening,
+                &mut why.rampup_widening,
+                &mut why.spread,
+                &mut why.skew,
+                &mut why.profit_taking,
+            ] {
+                *value = self.scale(*value);
+            }
+            why.spread_clamped_to = why.spread_clamped_to.map(|clamp| self.scale(clamp));
+        }
+        result
+    }
+
+    /// A whole step's results converted for display
+    pub fn step(self, results: &HashMap<String, StepResult>) -> HashMap<String, StepResult> {
+        results
+            .iter()
+            .map(|(name, result)| (name.clone(), self.step_result(result)))
+            .collect()
+    }
+}
+
+/// End-of-run aggregates plus per-market snapshots, for scripts and CI
+#[derive(Debug, serde::Serialize)]
+pub struct Summary {
+    pub seed: u64,
+    pub price_seed: u64,
+    pub steps: u64,
+    // PnL, drawdown and fill totals exclude this many opening steps
+    pub warmup_steps: u64,
+    // totals only count markets active for at least `min_active_steps`
+    pub min_active_steps: u64,
+    pub active_markets: usize,
+    pub total_pnl: f64,
+    // per-market PnL and Sharpe averaged with active (traded) steps as
+    // weights, so a market that resolved early counts for less
+    pub step_weighted_pnl: f64,
+    pub step_weighted_sharpe: f64,
+    pub total_fills: u64,
+    pub total_notional: f64,
+    pub max_drawdown: f64, // worst single market
+    pub portfolio_max_drawdown: f64,
+    pub calmar_ratio: f64,
+    pub performance: PerformanceReport, // whole portfolio
+    pub rng_position: RngPosition, // where every RNG stream ended
+    pub markets: Vec<MarketSnapshot>,
+}
+
+impl Summary {
+    pub fn new(engine: &ExecutionEngine, display: PriceDisplay, min_active_steps: u64) -> Self {
+        let states: Vec<&MarketState> = engine
+            .states_in_order()
+            .into_iter()
+            .filter(|s| s.active_steps >= min_active_steps)
+            .collect();
+        let active_steps: u64 = states.iter().map(|s| s.active_steps).sum();
+        let step_weighted = |value: &dyn Fn(&MarketState) -> f64| {
+            if active_steps > 0 {
+                states.iter().map(|s| value(s) * s.active_steps as f64).sum::<f64>() / active_steps as f64
+            } else {
+                0.0
+            }
+        };
+        let step_weighted_pnl = step_weighted(&|s| s.reported_pnl());
+        let step_weighted_sharpe = step_weighted(&|s| {
+            PerformanceReport::new(&s.pnl_history, engine.risk_free, engine.steps_per_year).sharpe_ratio
+        });
+        let mut markets: Vec<MarketSnapshot> = engine
+            .markets
+            .keys()
+            .filter_map(|name| engine.snapshot(name))
+            .map(|snapshot| display.snapshot(snapshot))
+            .collect();
+        markets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Summary {
+            seed: engine.rng_seed,
+            price_seed: engine.price_seed,
+            steps: engine.time,
+            warmup_steps: engine.warmup_steps,
+            min_active_steps,
+            active_markets: states.len(),
+            total_pnl: states.iter().map(|s| s.reported_pnl()).sum(),
+            step_weighted_pnl,
+            step_weighted_sharpe,
+            total_fills: states.iter().map(|s| s.fill_count - s.warmup_fill_count).sum(),
+            total_notional: states.iter().map(|s| s.notional - s.warmup_notional).sum(),
+            max_drawdown: states.iter().map(|s| s.max_drawdown).fold(0.0, f64::max),
+            portfolio_max_drawdown: engine.portfolio_max_drawdown,
+            calmar_ratio: engine.calmar_ratio(),
+            performance: engine.portfolio_performance(),
+            rng_position: engine.rng_position(),
+            markets,
+        }
+    }
+}
+
+pub fn write_summary(summary: &Summary, out_path: &str) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    serde_json::to_writer_pretty(file, summary)?;
+    Ok(())
+}
 
 #[derive(Debug, serde::Serialize)]
 struct ReportRow {
@@ -13,26 +163,56 @@ struct ReportRow {
     pnl: f64,
     fill_count: u64,
     notional: f64,
+    fees_paid: f64,
+    gross_long_volume: f64,
+    gross_short_volume: f64,
     max_drawdown: f64,
+    cost_basis: Option<f64>, // empty when flat
+    realized_pnl: f64,
+    unrealized_pnl: f64,
+    avg_quoted_spread: f64,
+    breakeven_spread: f64,
+    structurally_profitable: bool,
+    toxicity_score: f64,
+    turnover: f64,
+    accrued_rebates: f64,
+    rng_seed: Option<u64>,
 }
 
 pub fn write_report(
     states: &HashMap<String, MarketState>,
     out_path: &str,
+    display: PriceDisplay,
 ) -> Result<(), Box<dyn Error>> {
     let file = File::create(out_path)?;
     let mut writer = Writer::from_writer(file);
 
-    for (name, state) in states {
+    let mut names: Vec<&String> = states.keys().collect();
+    names.sort();
+    for name in names {
+        let state = &states[name];
         let row = ReportRow {
             market: name.clone(),
-            mid: state.mid,
-            spread: state.spread,
+            mid: display.scale(state.mid),
+            spread: display.scale(state.spread),
             inventory: state.inventory,
-            pnl: state.pnl,
-            fill_count: state.fill_count,
-            notional: state.notional,
+            pnl: state.reported_pnl(),
+            fill_count: state.fill_count - state.warmup_fill_count,
+            notional: state.notional - state.warmup_notional,
+            fees_paid: state.fees_paid,
+            gross_long_volume: state.gross_long_volume,
+            gross_short_volume: state.gross_short_volume,
             max_drawdown: state.max_drawdown,
+            cost_basis: state.open_position_cost_basis().map(|basis| display.scale(basis)),
+            realized_pnl: state.realized_pnl,
+            unrealized_pnl: state.unrealized_pnl(),
+            avg_quoted_spread: display.scale(state.avg_quoted_spread()),
+            breakeven_spread: display.scale(state.breakeven_spread()),
+            structurally_profitable: state.avg_quoted_spread() >= state.breakeven_spread(),
+            toxicity_score: state.toxicity_score,
+            turnover: state.turnover(),
+            accrued_rebates: state.accrued_rebates,
+            rng_seed: state.rng_seed,
         };
         writer.serialize(row)?;
     }
@@ -40,3 +220,532 @@ pub fn write_report(
     writer.flush()?;
     Ok(())
 }
+
+#[derive(Debug, serde::Serialize)]
+struct FillRow {
+    market: String,
+    side: String,
+    size: f64,
+    price: f64,
+    realized_spread: f64,
+    markout: Option<f64>,
+    fee: f64,
+    net_edge: Option<f64>, // empty until the markout horizon has passed
+    step: u64,
+    timestamp: f64,
+}
+
+/// Write every recorded fill with its realized spread, one row per fill
+pub fn write_fills(
+    states: &HashMap<String, MarketState>,
+    out_path: &str,
+    display: PriceDisplay,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    let mut names: Vec<&String> = states.keys().collect();
+    names.sort();
+    for name in names {
+        for fill in &states[name].fills {
+            let row = FillRow {
+                market: name.clone(),
+                side: fill.side.clone(),
+                size: fill.size,
+                price: display.scale(fill.price),
+                realized_spread: display.scale(fill.realized_spread),
+                markout: fill.markout.map(|m| display.scale(m)),
+                fee: fill.fee,
+                net_edge: fill.net_edge(),
+                step: fill.step,
+                timestamp: fill.timestamp,
+            };
+            writer.serialize(row)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Streams trace steps as newline-delimited JSON, buffering `batch_size`
+/// steps between writes. Buffered steps are flushed on drop, so normal exit
+/// and panics lose nothing, and with `interrupt::install` a Ctrl-C stops the
+/// run between steps and flushes too; only a hard kill loses a batch.
+pub struct TraceWriter<W: Write> {
+    writer: W,
+    batch_size: usize,
+    buffer: Vec<u8>,
+    pending: usize,
+    display: PriceDisplay,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(writer: W, batch_size: usize) -> Self {
+        TraceWriter {
+            writer,
+            batch_size: batch_size.max(1),
+            buffer: Vec::new(),
+            pending: 0,
+            display: PriceDisplay::Probability,
+        }
+    }
+
+    /// Write prices in `display` units instead of probabilities
+    pub fn with_display(mut self, display: PriceDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    pub fn write_step(&mut self, step: &HashMap<String, StepResult>) -> io::Result<()> {
+        match self.display {
+            PriceDisplay::Probability => serde_json::to_writer(&mut self.buffer, &by_market(step))?,
+            display => serde_json::to_writer(&mut self.buffer, &by_market(&display.step(step)))?,
+        }
+        self.buffer.push(b'\n');
+        self.pending += 1;
+
+        if self.pending >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.writer.flush()?;
+        self.buffer.clear();
+        self.pending = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for TraceWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct UtilizationRow<'a> {
+    step: u64,
+    market: &'a str,
+    inventory_utilization_pct: Option<f64>,
+    exposure_utilization_pct: Option<f64>,
+}
+
+/// Write per-step inventory and exposure limit utilization for every market
+pub fn write_limit_utilization(
+    trace: &[HashMap<String, StepResult>],
+    out_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    for results in trace {
+        let mut names: Vec<&String> = results.keys().collect();
+        names.sort();
+        for name in names {
+            let result = &results[name];
+            writer.serialize(UtilizationRow {
+                step: result.step,
+                market: name,
+                inventory_utilization_pct: result.inventory_utilization,
+                exposure_utilization_pct: result.exposure_utilization,
+            })?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write Monte Carlo equity percentile bands (fan chart data), one row per step
+pub fn write_equity_bands(bands: &[EquityBand], out_path: &str) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    for band in bands {
+        writer.serialize(band)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize)]
+struct McRunRow {
+    seed: u64,
+    start_inventory: f64,
+    final_pnl: f64,
+}
+
+/// Write each Monte Carlo run's starting inventory and final PnL
+pub fn write_mc_runs(runs: &[McRun], out_path: &str) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    for run in runs {
+        writer.serialize(McRunRow {
+            seed: run.seed,
+            start_inventory: run.start_inventory,
+            final_pnl: run.final_pnl(),
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write fill volume bucketed by price (bins labelled in `display` units,
+/// columns) and step (rows), one block of rows per market
+pub fn write_fill_heatmap(
+    trace: &[HashMap<String, StepResult>],
+    out_path: &str,
+    price_bins: usize,
+    step_bins: usize,
+    display: PriceDisplay,
+) -> Result<(), Box<dyn Error>> {
+    let price_bins = price_bins.max(1);
+    let step_bins = step_bins.max(1).min(trace.len().max(1));
+
+    let mut names: Vec<&String> = trace.iter().flat_map(|step| step.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    let mut header = vec!["market".to_string(), "step_from".to_string(), "step_to".to_string()];
+    for bin in 0..price_bins {
+        let lo = bin as f64 / price_bins as f64;
+        let hi = (bin + 1) as f64 / price_bins as f64;
+        header.push(format!("{:.2}-{:.2}", display.scale(lo), display.scale(hi)));
+    }
+    writer.write_record(&header)?;
+
+    for name in names {
+        let mut cells = vec![vec![0.0; price_bins]; step_bins];
+        for (step, results) in trace.iter().enumerate() {
+            let Some(result) = results.get(name) else { continue };
+            let row = step * step_bins / trace.len();
+            for fill in &result.fills {
+                let col = ((fill.price * price_bins as f64) as usize).min(price_bins - 1);
+                cells[row][col] += fill.size;
+            }
+        }
+
+        for (row, volumes) in cells.iter().enumerate() {
+            let step_from = row * trace.len() / step_bins;
+            let step_to = (row + 1) * trace.len() / step_bins - 1;
+            let mut record = vec![name.clone(), step_from.to_string(), step_to.to_string()];
+            record.extend(volumes.iter().map(|v| v.to_string()));
+            writer.write_record(&record)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize)]
+struct PositionChangeRow<'a> {
+    market: &'a str,
+    step_from: u64,
+    step_to: u64,
+    start_inventory: f64,
+    end_inventory: f64,
+    net_change: f64,
+}
+
+/// Write the net inventory change over each window of `window` steps per
+/// market, so directional drift shows through intra-window churn
+pub fn write_position_changes(
+    trace: &[HashMap<String, StepResult>],
+    out_path: &str,
+    window: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut names: Vec<&String> = trace.iter().flat_map(|step| step.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    for name in names {
+        let mut previous_end: Option<f64> = None;
+        for chunk in trace.chunks(window.max(1)) {
+            let results: Vec<&StepResult> = chunk.iter().filter_map(|step| step.get(name)).collect();
+            let (Some(first), Some(last)) = (results.first(), results.last()) else { continue };
+
+            // Before the first window, back out that step's own fills
+            let start_inventory = previous_end.unwrap_or_else(|| {
+                first.inventory
+                    - first
+                        .fills
+                        .iter()
+                        .map(|f| if f.side == "buy" { f.size } else { -f.size })
+                        .sum::<f64>()
+            });
+            writer.serialize(PositionChangeRow {
+                market: name,
+                step_from: first.step,
+                step_to: last.step,
+                start_inventory,
+                end_inventory: last.inventory,
+                net_change: last.inventory - start_inventory,
+            })?;
+            previous_end = Some(last.inventory);
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize)]
+struct FunnelRow<'a> {
+    market: &'a str,
+    quotes_posted: u64,
+    orders_seen: u64,
+    orders_crossed: u64,
+    orders_filled: u64,
+    volume_filled: f64,
+}
+
+/// Write each market's quote-to-fill funnel: quotes posted, orders seen,
+/// orders crossing the quote, orders filled after caps, and filled volume
+pub fn write_funnel(engine: &ExecutionEngine, out_path: &str) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    let mut names: Vec<&String> = engine.market_makers.keys().collect();
+    names.sort();
+    for name in names {
+        let funnel = engine.market_makers[name].funnel;
+        writer.serialize(FunnelRow {
+            market: name,
+            quotes_posted: funnel.quotes_posted,
+            orders_seen: funnel.orders_seen,
+            orders_crossed: funnel.orders_crossed,
+            orders_filled: funnel.orders_filled,
+            volume_filled: funnel.volume_filled,
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ActivityRow<'a> {
+    step: u64,
+    market: &'a str,
+    trades_per_step: f64,
+    notional_per_step: f64,
+}
+
+/// Write rolling trades-per-step and notional-per-step over the last
+/// `window` steps each market traded; the first rows average over the steps
+/// available so far
+pub fn write_activity_rate(
+    trace: &[HashMap<String, StepResult>],
+    out_path: &str,
+    window: usize,
+) -> Result<(), Box<dyn Error>> {
+    let window = window.max(1);
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    let mut recent: HashMap<&str, VecDeque<(f64, f64)>> = HashMap::new();
+    for results in trace {
+        let mut names: Vec<&String> = results.keys().collect();
+        names.sort();
+        for name in names {
+            let result = &results[name];
+            let notional: f64 = result.fills.iter().map(|f| f.size * f.price).sum();
+            let steps = recent.entry(name).or_default();
+            steps.push_back((result.fills.len() as f64, notional));
+            if steps.len() > window {
+                steps.pop_front();
+            }
+
+            let count = steps.len() as f64;
+            writer.serialize(ActivityRow {
+                step: result.step,
+                market: name,
+                trades_per_step: steps.iter().map(|s| s.0).sum::<f64>() / count,
+                notional_per_step: steps.iter().map(|s| s.1).sum::<f64>() / count,
+            })?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the settlement ledger, one row per settled market
+pub fn write_settlement_ledger(
+    ledger: &[SettlementRecord],
+    out_path: &str,
+    display: PriceDisplay,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    for record in ledger {
+        writer.serialize(SettlementRecord {
+            settlement_price: display.scale(record.settlement_price),
+            ..record.clone()
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// A single-step PnL change far outside a market's usual step-to-step moves
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PnlJump {
+    pub step: u64,
+    pub market: String,
+    pub change: f64,
+    pub z_score: f64, // standard deviations from the market's mean step change
+}
+
+/// Flag steps whose PnL change is more than `threshold` standard deviations
+/// from that market's mean step change, sorted by step then market
+pub fn detect_pnl_jumps(trace: &[HashMap<String, StepResult>], threshold: f64) -> Vec<PnlJump> {
+    let mut changes: HashMap<&str, Vec<(u64, f64)>> = HashMap::new();
+    let mut previous: HashMap<&str, f64> = HashMap::new();
+    for results in trace {
+        for (name, result) in results {
+            let last = previous.insert(name, result.pnl).unwrap_or(0.0);
+            changes.entry(name).or_default().push((result.step, result.pnl - last));
+        }
+    }
+
+    let mut jumps = Vec::new();
+    for (name, series) in changes {
+        let n = series.len() as f64;
+        let mean = series.iter().map(|c| c.1).sum::<f64>() / n;
+        let std = (series.iter().map(|c| (c.1 - mean).powi(2)).sum::<f64>() / n).sqrt();
+        if std == 0.0 {
+            continue;
+        }
+        for (step, change) in series {
+            let z_score = (change - mean) / std;
+            if z_score.abs() > threshold {
+                jumps.push(PnlJump {
+                    step,
+                    market: name.to_string(),
+                    change,
+                    z_score,
+                });
+            }
+        }
+    }
+
+    jumps.sort_by(|a, b| a.step.cmp(&b.step).then_with(|| a.market.cmp(&b.market)));
+    jumps
+}
+
+pub fn write_pnl_jumps(jumps: &[PnlJump], out_path: &str) -> Result<(), Box<dyn Error>> {
+    let file = File::create(out_path)?;
+    let mut writer = Writer::from_writer(file);
+
+    for jump in jumps {
+        writer.serialize(jump)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn engine(seed: u64) -> ExecutionEngine {
+        let markets = ["alpha", "beta", "gamma"]
+            .iter()
+            .map(|name| (name.to_string(), MarketState::new(name, 0.5)))
+            .collect();
+        ExecutionEngine::new(markets, seed).unwrap()
+    }
+
+    fn streamed(batch_size: usize) -> Vec<u8> {
+        streamed_in(batch_size, PriceDisplay::Probability)
+    }
+
+    fn streamed_in(batch_size: usize, display: PriceDisplay) -> Vec<u8> {
+        let mut engine = engine(208);
+        let mut out = Vec::new();
+        {
+            let mut writer = TraceWriter::new(&mut out, batch_size).with_display(display);
+            for _ in 0..50 {
+                writer.write_step(&engine.step()).unwrap();
+            }
+            // the last partial batch is left to the drop flush
+        }
+        out
+    }
+
+    #[test]
+    fn batched_trace_matches_unbatched() {
+        let unbatched = streamed(1);
+        assert_eq!(unbatched.iter().filter(|&&b| b == b'\n').count(), 50);
+        assert_eq!(streamed(7), unbatched);
+        assert_eq!(streamed(1000), unbatched);
+    }
+
+    #[test]
+    fn cents_are_probabilities_times_one_hundred() {
+        let lines = |display| -> Vec<BTreeMap<String, StepResult>> {
+            let out = streamed_in(1, display);
+            std::str::from_utf8(&out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+        };
+        let probability = lines(PriceDisplay::Probability);
+        let cents = lines(PriceDisplay::Cents);
+        let close = |cents: f64, probability: f64| (cents - probability * 100.0).abs() < 1e-9;
+
+        let mut fills = 0;
+        for (p_step, c_step) in probability.iter().zip(&cents) {
+            for (name, p) in p_step {
+                let c = &c_step[name];
+                assert!(close(c.mid, p.mid) && close(c.spread, p.spread));
+                assert_eq!((c.inventory, c.pnl), (p.inventory, p.pnl));
+                for (c_fill, p_fill) in c.fills.iter().zip(&p.fills) {
+                    assert!(close(c_fill.price, p_fill.price));
+                    assert_eq!(c_fill.size, p_fill.size);
+                    fills += 1;
+                }
+            }
+        }
+        assert!(fills > 0);
+        assert_eq!(cents.len(), 50);
+    }
+
+    #[test]
+    fn step_weighting_discounts_a_market_that_resolved_early() {
+        let mut short_lived = engine(241);
+        short_lived.markets.get_mut("alpha").unwrap().resolution_step = Some(3);
+        short_lived.run(200);
+        let alpha = &short_lived.markets["alpha"];
+        assert!(alpha.active_steps <= 3);
+        assert!(short_lived.markets["beta"].active_steps > 50);
+
+        let summary = Summary::new(&short_lived, PriceDisplay::Probability, 0);
+        let mean_pnl = summary.total_pnl / 3.0;
+        let mean_sharpe = summary.markets.iter().map(|s| s.performance.sharpe_ratio).sum::<f64>() / 3.0;
+        assert!((summary.step_weighted_pnl - mean_pnl).abs() > 1e-3);
+        assert!((summary.step_weighted_sharpe - mean_sharpe).abs() > 1e-3);
+
+        // Steps without a fill are not active
+        let mut quiet = engine(241);
+        quiet.set_orders_per_tick(0, 0).unwrap();
+        quiet.run(50);
+        assert!(quiet.markets.values().all(|s| s.active_steps == 0));
+    }
+}
@@ -1,14 +1,19 @@
-mod execution_engine;
-mod logger;
-mod market_maker;
-mod market_state;
-
-use execution_engine::ExecutionEngine;
-use market_state::MarketState;
+use quant_bot_rust::execution_engine::{CapitalExhaustion, CheckpointConfig, NonFinitePolicy};
+use quant_bot_rust::logger::{PriceDisplay, Summary, TraceWriter};
+use quant_bot_rust::{config, execution_engine, interrupt, logger, monte_carlo};
+use quant_bot_rust::{
+    BotError, CovarianceModel, ExecutionEngine, HistoricalFlow, MarketState, OrderFlowModel, PolledFeed, PriceFeedSource, RngPosition, StepResult,
+};
 use std::collections::HashMap;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Output paths as the `&str` the writers take
+fn path_str(path: &Path) -> Result<&str, BotError> {
+    path.to_str().ok_or_else(|| BotError::NonUtf8Path(path.to_path_buf()))
+}
 
 fn build_markets() -> HashMap<String, MarketState> {
     let mut markets = HashMap::new();
@@ -35,36 +40,325 @@ fn build_markets() -> HashMap<String, MarketState> {
     markets
 }
 
-fn run_demo() -> Result<String, Box<dyn std::error::Error>> {
-    println!("🚀 Quant Execution Bot - Rust Implementation");
-    println!("============================================\n");
+/// How much the demo prints; output files are written at every level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,   // summary statistics only
+    Normal,  // per-market breakdown and summary
+    Verbose, // plus per-step progress
+}
 
-    let markets = build_markets();
-    let mut engine = ExecutionEngine::new(markets, 123);
+/// Command-line switches for the demo run
+struct DemoOptions {
+    price_display: PriceDisplay,
+    verbosity: Verbosity,
+    // stream the trace as NDJSON, flushing every N steps
+    trace_batch: Option<usize>,
+    // JSON covariance model for correlated mid diffusion
+    covariance_path: Option<String>,
+    // file or http:// endpoint of reference mids, polled every N steps
+    price_feed: Option<PriceFeedSource>,
+    // CSV of recorded taker orders to replay instead of simulated flow
+    replay_path: Option<String>,
+    poll_interval: u64,
+    checkpoint_interval: Option<u64>,
+    resume_path: Option<String>,
+    // extra seeds to run for equity confidence bands
+    mc_runs: Option<u64>,
+    // start each Monte Carlo run at random inventories within this fraction of the limits
+    mc_random_inventory: Option<f64>,
+    // (price bins, step bins) for the fill heatmap
+    heatmap_bins: (usize, usize),
+    // steps per window for the net position change export
+    position_window: usize,
+    // rolling window for the activity rate export
+    activity_window: usize,
+    // standard deviations a step's PnL change must exceed to be flagged
+    jump_threshold: f64,
+    config_path: Option<String>,
+    steps: u64,
+    // order flow seed
+    seed: u64,
+    // output directory; defaults to the current directory
+    out_dir: Option<PathBuf>,
+    // diffusion seed; defaults to the flow seed
+    price_seed: Option<u64>,
+    // per-market loadings on a shared factor shock
+    betas: HashMap<String, f64>,
+    steps_per_year: Option<f64>,
+    non_finite: NonFinitePolicy,
+    capital_exhaustion: Option<CapitalExhaustion>,
+    // markets that traded on fewer steps are left out of summary totals
+    min_active_steps: u64,
+    // opening steps left out of the reported metrics. This and the two
+    // switches below only change a resumed checkpoint when passed
+    warmup_steps: Option<u64>,
+    // record the maker config in every step result
+    audit_config: bool,
+    explain: bool,
+    // JSON RngPosition to fast-forward the RNG streams to
+    rng_position_path: Option<String>,
+    flow_model: Option<OrderFlowModel>,
+    // (min, max) taker orders per tick
+    orders_per_tick: Option<(u32, u32)>,
+}
 
-    println!("📊 Running simulation with 200 steps...\n");
-    let trace = engine.run(200);
+fn parse_options(args: &[String]) -> Result<DemoOptions, String> {
+    let mut options = DemoOptions {
+        price_display: PriceDisplay::Probability,
+        verbosity: Verbosity::Normal,
+        trace_batch: None,
+        covariance_path: None,
+        price_feed: None,
+        replay_path: None,
+        poll_interval: 10,
+        checkpoint_interval: None,
+        resume_path: None,
+        mc_runs: None,
+        mc_random_inventory: None,
+        heatmap_bins: (10, 10),
+        position_window: 10,
+        activity_window: 20,
+        jump_threshold: 4.0,
+        config_path: None,
+        steps: 200,
+        seed: 123,
+        out_dir: None,
+        price_seed: None,
+        betas: HashMap::new(),
+        steps_per_year: None,
+        non_finite: NonFinitePolicy::Reject,
+        capital_exhaustion: None,
+        min_active_steps: 0,
+        warmup_steps: None,
+        audit_config: false,
+        explain: false,
+        rng_position_path: None,
+        flow_model: None,
+        orders_per_tick: None,
+    };
 
-    // Get output directory (current executable's directory or current dir)
-    let out_dir = env::current_dir()?;
-    let csv_path = out_dir.join("simulation_report.csv");
-    let trace_path = out_dir.join("trace.json");
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--cents" => options.price_display = PriceDisplay::Cents,
+            "--quiet" | "-q" => options.verbosity = Verbosity::Quiet,
+            "--verbose" | "-v" => options.verbosity = Verbosity::Verbose,
+            "--audit-config" => options.audit_config = true,
+            "--explain" => options.explain = true,
+            "--trace-batch" => {
+                let value = iter.next().ok_or("--trace-batch needs a value")?;
+                let batch = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid --trace-batch value: {}", value))?;
+                options.trace_batch = Some(batch);
+            }
+            "--config" => {
+                let value = iter.next().ok_or("--config needs a path")?;
+                options.config_path = Some(value.clone());
+            }
+            "--covariance" => {
+                let value = iter.next().ok_or("--covariance needs a path")?;
+                options.covariance_path = Some(value.clone());
+            }
+            "--price-feed" => {
+                let value = iter.next().ok_or("--price-feed needs a path or http:// URL")?;
+                options.price_feed = Some(PriceFeedSource::parse(value).map_err(|e| e.to_string())?);
+            }
+            "--poll-interval" => {
+                let value = iter.next().ok_or("--poll-interval needs a value")?;
+                options.poll_interval = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|interval| *interval > 0)
+                    .ok_or_else(|| format!("invalid --poll-interval value: {}", value))?;
+            }
+            "--checkpoint-interval" => {
+                let value = iter.next().ok_or("--checkpoint-interval needs a value")?;
+                let interval = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --checkpoint-interval value: {}", value))?;
+                options.checkpoint_interval = Some(interval);
+            }
+            "--resume" => {
+                let value = iter.next().ok_or("--resume needs a checkpoint path")?;
+                options.resume_path = Some(value.clone());
+            }
+            "--mc-runs" => {
+                let value = iter.next().ok_or("--mc-runs needs a value")?;
+                let runs = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --mc-runs value: {}", value))?;
+                options.mc_runs = Some(runs);
+            }
+            "--mc-random-inventory" => {
+                let value = iter.next().ok_or("--mc-random-inventory needs a fraction")?;
+                let fraction = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|f| (0.0..=1.0).contains(f))
+                    .ok_or_else(|| format!("invalid --mc-random-inventory value: {}", value))?;
+                options.mc_random_inventory = Some(fraction);
+            }
+            "--orders-per-tick" => {
+                let value = iter.next().ok_or("--orders-per-tick needs <min>,<max>")?;
+                let invalid = || format!("invalid --orders-per-tick value: {}", value);
+                let (min, max) = value.split_once(',').ok_or_else(invalid)?;
+                let min = min.parse::<u32>().map_err(|_| invalid())?;
+                let max = max.parse::<u32>().map_err(|_| invalid())?;
+                options.orders_per_tick = Some((min, max));
+            }
+            "--heatmap-bins" => {
+                let value = iter.next().ok_or("--heatmap-bins needs <price>,<step>")?;
+                let invalid = || format!("invalid --heatmap-bins value: {}", value);
+                let (price, step) = value.split_once(',').ok_or_else(invalid)?;
+                let price = price.parse::<usize>().map_err(|_| invalid())?;
+                let step = step.parse::<usize>().map_err(|_| invalid())?;
+                options.heatmap_bins = (price, step);
+            }
+            "--position-window" => {
+                let value = iter.next().ok_or("--position-window needs a value")?;
+                let window = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|w| *w > 0)
+                    .ok_or_else(|| format!("invalid --position-window value: {}", value))?;
+                options.position_window = window;
+            }
+            "--capital-exhaustion" => {
+                let value = iter.next().ok_or("--capital-exhaustion needs ignore, halt-market or halt-portfolio")?;
+                options.capital_exhaustion = Some(match value.as_str() {
+                    "ignore" => CapitalExhaustion::Ignore,
+                    "halt-market" => CapitalExhaustion::HaltMarket,
+                    "halt-portfolio" => CapitalExhaustion::HaltPortfolio,
+                    _ => return Err(format!("invalid --capital-exhaustion value: {}", value)),
+                });
+            }
+            "--non-finite" => {
+                let value = iter.next().ok_or("--non-finite needs reject or skip")?;
+                options.non_finite = match value.as_str() {
+                    "reject" => NonFinitePolicy::Reject,
+                    "skip" => NonFinitePolicy::Skip,
+                    _ => return Err(format!("invalid --non-finite value: {}", value)),
+                };
+            }
+            "--min-active-steps" => {
+                let value = iter.next().ok_or("--min-active-steps needs a value")?;
+                let steps = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --min-active-steps value: {}", value))?;
+                options.min_active_steps = steps;
+            }
+            "--warmup-steps" => {
+                let value = iter.next().ok_or("--warmup-steps needs a value")?;
+                let steps = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --warmup-steps value: {}", value))?;
+                options.warmup_steps = Some(steps);
+            }
+            "--activity-window" => {
+                let value = iter.next().ok_or("--activity-window needs a value")?;
+                let window = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|w| *w > 0)
+                    .ok_or_else(|| format!("invalid --activity-window value: {}", value))?;
+                options.activity_window = window;
+            }
+            "--rng-position" => {
+                let value = iter.next().ok_or("--rng-position needs a path")?;
+                options.rng_position_path = Some(value.clone());
+            }
+            "--jump-threshold" => {
+                let value = iter.next().ok_or("--jump-threshold needs a value")?;
+                let threshold = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|t| *t > 0.0)
+                    .ok_or_else(|| format!("invalid --jump-threshold value: {}", value))?;
+                options.jump_threshold = threshold;
+            }
+            "--flow" => {
+                let value = iter.next().ok_or("--flow needs mid-biased, poisson:<lambda> or adversarial")?;
+                let invalid = || format!("invalid --flow value: {}", value);
+                options.flow_model = Some(match value.split_once(':') {
+                    None if value == "mid-biased" => OrderFlowModel::MidBiased,
+                    None if value == "adversarial" => OrderFlowModel::Adversarial,
+                    Some(("poisson", lambda)) => OrderFlowModel::Poisson {
+                        lambda: lambda.parse::<f64>().ok().filter(|l| *l >= 0.0).ok_or_else(invalid)?,
+                    },
+                    _ => return Err(invalid()),
+                });
+            }
+            "--replay" => {
+                let value = iter.next().ok_or("--replay needs a path")?;
+                options.replay_path = Some(value.clone());
+            }
+            "--steps" => {
+                let value = iter.next().ok_or("--steps needs a value")?;
+                let steps = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --steps value: {}", value))?;
+                options.steps = steps;
+            }
+            "--seed" => {
+                let value = iter.next().ok_or("--seed needs a value")?;
+                let seed = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --seed value: {}", value))?;
+                options.seed = seed;
+            }
+            "--out-dir" => {
+                let value = iter.next().ok_or("--out-dir needs a path")?;
+                options.out_dir = Some(PathBuf::from(value));
+            }
+            "--betas" => {
+                let value = iter.next().ok_or("--betas needs market=beta pairs")?;
+                for pair in value.split(',') {
+                    let beta = pair
+                        .split_once('=')
+                        .and_then(|(market, beta)| Some((market.to_string(), beta.parse::<f64>().ok()?)))
+                        .filter(|(_, beta)| beta.is_finite())
+                        .ok_or_else(|| format!("invalid --betas entry: {}", pair))?;
+                    options.betas.insert(beta.0, beta.1);
+                }
+            }
+            "--price-seed" => {
+                let value = iter.next().ok_or("--price-seed needs a value")?;
+                let seed = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --price-seed value: {}", value))?;
+                options.price_seed = Some(seed);
+            }
+            "--steps-per-year" => {
+                let value = iter.next().ok_or("--steps-per-year needs a value")?;
+                let steps = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| *s > 0.0)
+                    .ok_or_else(|| format!("invalid --steps-per-year value: {}", value))?;
+                options.steps_per_year = Some(steps);
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
 
-    // Write CSV report
-    logger::write_report(&engine.markets, csv_path.to_str().unwrap())?;
-    println!("✅ Simulation complete. Report written to: {}", csv_path.display());
+    Ok(options)
+}
 
-    // Write trace JSON
-    let trace_json = serde_json::to_string_pretty(&trace)?;
-    let mut trace_file = File::create(&trace_path)?;
-    trace_file.write_all(trace_json.as_bytes())?;
-    println!("✅ Trace data written to: {}\n", trace_path.display());
+fn print_progress(engine: &ExecutionEngine, results: &HashMap<String, StepResult>) {
+    let fills: usize = results.values().map(|r| r.fills.len()).sum();
+    let total_pnl = engine.total_pnl();
+    println!("  step {:>4}: {:>2} fills, total pnl {:.4}", engine.time, fills, total_pnl);
+}
 
-    // Print final market states
+fn print_market_states(engine: &ExecutionEngine, price_display: PriceDisplay) {
     println!("📈 Final Market States:");
     println!("------------------------");
-    for (name, state) in &engine.markets {
-        let snapshot = state.snapshot();
+    for state in engine.states_in_order() {
+        let name = &state.name;
+        let Some(snapshot) = engine.snapshot(name) else { continue };
+        let snapshot = price_display.snapshot(snapshot);
         println!(
             "\n🏪 {} {{",
             name
@@ -72,33 +366,372 @@ fn run_demo() -> Result<String, Box<dyn std::error::Error>> {
         println!("    mid: {:.4}", snapshot.mid);
         println!("    spread: {:.4}", snapshot.spread);
         println!("    inventory: {:.2}", snapshot.inventory);
-        println!("    pnl: {:.4}", snapshot.pnl);
+        match snapshot.cost_basis {
+            Some(basis) => println!(
+                "    cost_basis: {:.4} vs mid {:.4} (unrealized {:.4})",
+                basis, snapshot.mid, snapshot.unrealized_pnl
+            ),
+            None => println!("    cost_basis: n/a (flat)"),
+        }
+        println!(
+            "    pnl: {:.4} (realized {:.4} + unrealized {:.4})",
+            snapshot.pnl, snapshot.realized_pnl, snapshot.unrealized_pnl
+        );
         println!("    fill_count: {}", snapshot.fill_count);
         println!("    notional: {:.2}", snapshot.notional);
+        println!("    fees_paid: {:.4}", snapshot.fees_paid);
+        println!(
+            "    gross_volume: long {:.2} / short {:.2}",
+            snapshot.gross_long_volume, snapshot.gross_short_volume
+        );
         println!("    max_drawdown: {:.4}", snapshot.max_drawdown);
+        println!(
+            "    breakeven_spread: {:.4} (avg quoted {:.4})",
+            snapshot.breakeven_spread, snapshot.avg_quoted_spread
+        );
+        println!("    toxicity_score: {:.2}", snapshot.toxicity_score);
+        println!("    turnover: {:.2}", snapshot.turnover);
+        println!("    calmar_ratio: {:.2}", snapshot.calmar_ratio);
+        let performance = snapshot.performance;
+        println!(
+            "    sharpe: {:.2}  sortino: {:.2}  annualized_vol: {:.4}",
+            performance.sharpe_ratio, performance.sortino_ratio, performance.annualized_volatility
+        );
+        let clamps = snapshot.quote_clamps;
+        println!(
+            "    quote_clamps: min_spread={} max_spread={} mid_shade={} bid_floor={} ask_ceiling={} crossed={} fill_price={}",
+            clamps.min_spread, clamps.max_spread, clamps.mid_shade,
+            clamps.bid_floor, clamps.ask_ceiling, clamps.crossed, clamps.fill_price
+        );
+        if let Some(seed) = snapshot.rng_seed {
+            println!("    rng_seed: {}", seed);
+        }
         println!("}}");
     }
+}
+
+fn run_demo(options: &DemoOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let normal = options.verbosity >= Verbosity::Normal;
+    let verbose = options.verbosity >= Verbosity::Verbose;
+
+    if normal {
+        println!("🚀 Quant Execution Bot - Rust Implementation");
+        println!("============================================\n");
+    }
+
+    let price_display = options.price_display;
+    let total_steps = options.steps;
+
+    let out_dir = match &options.out_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            dir.clone()
+        }
+        None => env::current_dir()?,
+    };
+
+    let (mut markets, maker_configs) = match &options.config_path {
+        Some(path) => {
+            let loaded = config::load_config(path)?;
+            (loaded.markets, loaded.makers)
+        }
+        None => (build_markets(), HashMap::new()),
+    };
+
+    let mut engine = match &options.resume_path {
+        Some(path) => {
+            let engine = ExecutionEngine::load_checkpoint(path)?;
+            if normal {
+                println!("⏩ Resumed from {} at step {}\n", path, engine.time);
+            }
+            engine
+        }
+        None => {
+            let mut engine = ExecutionEngine::with_policy(
+                markets.clone(),
+                options.seed,
+                options.price_seed.unwrap_or(options.seed),
+                options.non_finite,
+            )?;
+            for (name, config) in &maker_configs {
+                engine.set_maker_config(name, config.clone())?;
+            }
+            engine
+        }
+    };
+    // Monte Carlo runs start from the markets construction kept
+    markets.retain(|name, _| engine.markets.contains_key(name));
+    if let Some(path) = &options.covariance_path {
+        engine.covariance = Some(CovarianceModel::from_json_file(path)?);
+    }
+    if let Some(source) = &options.price_feed {
+        engine.price_feed = Some(PolledFeed::new(source.clone().into_feed(), options.poll_interval));
+    }
+    // Betas from --betas or the config switch the shared factor on
+    if !options.betas.is_empty() || engine.markets.values().any(|state| state.beta != 0.0) {
+        engine.set_correlation(&options.betas)?;
+    }
+    if let Some(path) = &options.replay_path {
+        engine.historical_flow = Some(HistoricalFlow::from_csv_path(path)?);
+    }
+    if let Some(path) = &options.rng_position_path {
+        let position: RngPosition = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        engine.set_rng_position(&position)?;
+    }
+    if let Some(warmup_steps) = options.warmup_steps {
+        engine.warmup_steps = warmup_steps;
+    }
+    if options.audit_config {
+        engine.audit_config = true;
+    }
+    if options.explain {
+        engine.explain_quotes = true;
+    }
+    if let Some(policy) = options.capital_exhaustion {
+        engine.capital_exhaustion = policy;
+    }
+    if let Some(model) = options.flow_model {
+        engine.flow_model = Box::new(model);
+    }
+    if let Some((min, max)) = options.orders_per_tick {
+        engine.set_orders_per_tick(min, max)?;
+    }
+    if let Some(steps_per_year) = options.steps_per_year {
+        engine.steps_per_year = steps_per_year;
+    }
+    if let Some(interval) = options.checkpoint_interval {
+        engine.checkpoint = Some(CheckpointConfig {
+            interval,
+            dir: out_dir.join("checkpoints"),
+        });
+    }
+    let steps = total_steps.saturating_sub(engine.time) as usize;
+
+    let csv_path = out_dir.join("simulation_report.csv");
+    let trace_path = out_dir.join("trace.json");
+    let ndjson_path = out_dir.join("trace.ndjson");
+    let fills_path = out_dir.join("fills.csv");
+    let funnel_path = out_dir.join("funnel.csv");
+    let settlements_path = out_dir.join("settlements.csv");
+    let summary_path = out_dir.join("summary.json");
+    let utilization_path = out_dir.join("limit_utilization.csv");
+    let bands_path = out_dir.join("equity_bands.csv");
+    let mc_runs_path = out_dir.join("mc_runs.csv");
+    let heatmap_path = out_dir.join("fill_heatmap.csv");
+    let positions_path = out_dir.join("position_changes.csv");
+    let activity_path = out_dir.join("activity_rate.csv");
+    let jumps_path = out_dir.join("pnl_jumps.csv");
+
+    if normal {
+        println!("📊 Running simulation with {} steps...\n", steps);
+    }
+    let trace = match options.trace_batch {
+        Some(batch_size) => {
+            // Stream steps to disk instead of holding the whole trace
+            let mut writer = TraceWriter::new(File::create(&ndjson_path)?, batch_size).with_display(price_display);
+            engine.run_streaming_with(steps, &mut writer, |engine, results| {
+                if verbose {
+                    print_progress(engine, results);
+                }
+            })?;
+            None
+        }
+        None if verbose => Some(engine.run_with(steps, print_progress)),
+        None => {
+            let (trace, stats) = engine.run_timed(steps);
+            if normal {
+                println!(
+                    "⏱️  {} steps in {:.3}s ({:.0} steps/s, slowest step {:.3}ms)\n",
+                    stats.total_steps,
+                    stats.elapsed.as_secs_f64(),
+                    stats.steps_per_sec,
+                    stats.slowest_step.as_secs_f64() * 1000.0
+                );
+            }
+            Some(trace)
+        }
+    };
+    if verbose {
+        println!();
+    }
+    if normal && engine.replay_exhausted() {
+        println!("⏹️  Replay exhausted after {} steps\n", engine.time);
+    }
+    // Ctrl-C stopped the run; outputs are still written for the steps run
+    let interrupted = interrupt::requested();
+    if interrupted {
+        eprintln!("⏹️  Interrupted after {} steps, writing outputs", engine.time);
+    }
+
+    // Write CSV report
+    logger::write_report(&engine.markets, path_str(&csv_path)?, price_display)?;
+    if normal {
+        println!("✅ Simulation complete. Report written to: {}", csv_path.display());
+    }
+
+    // Write per-fill blotter
+    logger::write_fills(&engine.markets, path_str(&fills_path)?, price_display)?;
+    if normal {
+        println!("✅ Fills written to: {}", fills_path.display());
+    }
+
+    logger::write_funnel(&engine, path_str(&funnel_path)?)?;
+    if normal {
+        println!("✅ Quote-to-fill funnel written to: {}", funnel_path.display());
+    }
+
+    if !engine.settlement_ledger.is_empty() {
+        logger::write_settlement_ledger(&engine.settlement_ledger, path_str(&settlements_path)?, price_display)?;
+        if normal {
+            println!("✅ Settlement ledger written to: {}", settlements_path.display());
+        }
+    }
+
+    // Write trace JSON
+    match trace {
+        Some(trace) => {
+            let trace_json = match price_display {
+                PriceDisplay::Probability => {
+                    serde_json::to_string_pretty(&trace.iter().map(execution_engine::by_market).collect::<Vec<_>>())?
+                }
+                display => {
+                    let displayed: Vec<_> = trace.iter().map(|step| display.step(step)).collect();
+                    serde_json::to_string_pretty(&displayed.iter().map(execution_engine::by_market).collect::<Vec<_>>())?
+                }
+            };
+            let mut trace_file = File::create(&trace_path)?;
+            trace_file.write_all(trace_json.as_bytes())?;
+            if normal {
+                println!("✅ Trace data written to: {}", trace_path.display());
+            }
+
+            logger::write_limit_utilization(&trace, path_str(&utilization_path)?)?;
+            if normal {
+                println!("✅ Limit utilization written to: {}", utilization_path.display());
+            }
+
+            let (price_bins, step_bins) = options.heatmap_bins;
+            logger::write_fill_heatmap(&trace, path_str(&heatmap_path)?, price_bins, step_bins, price_display)?;
+            if normal {
+                println!("✅ Fill heatmap written to: {}", heatmap_path.display());
+            }
+
+            logger::write_position_changes(&trace, path_str(&positions_path)?, options.position_window)?;
+            if normal {
+                println!("✅ Position changes written to: {}", positions_path.display());
+            }
+
+            logger::write_activity_rate(&trace, path_str(&activity_path)?, options.activity_window)?;
+            if normal {
+                println!("✅ Activity rate written to: {}", activity_path.display());
+            }
+
+            let jumps = logger::detect_pnl_jumps(&trace, options.jump_threshold);
+            logger::write_pnl_jumps(&jumps, path_str(&jumps_path)?)?;
+            if normal {
+                println!(
+                    "✅ {} PnL jumps beyond {} sigma written to: {}\n",
+                    jumps.len(),
+                    options.jump_threshold,
+                    jumps_path.display()
+                );
+            }
+        }
+        None if normal => println!("✅ Trace data streamed to: {}\n", ndjson_path.display()),
+        None => {}
+    }
+
+    // Print final market states
+    if normal {
+        print_market_states(&engine, price_display);
+    }
 
     // Print summary statistics
-    println!("\n📊 Summary Statistics:");
+    let summary = Summary::new(&engine, price_display, options.min_active_steps);
+    if normal {
+        println!();
+    }
+    println!("📊 Summary Statistics:");
     println!("----------------------");
-    let total_pnl: f64 = engine.markets.values().map(|s| s.pnl).sum();
-    let total_fills: u64 = engine.markets.values().map(|s| s.fill_count).sum();
-    let total_notional: f64 = engine.markets.values().map(|s| s.notional).sum();
-    let max_dd: f64 = engine.markets.values().map(|s| s.max_drawdown).fold(0.0, f64::max);
+    if summary.active_markets < engine.markets.len() {
+        println!(
+            "Active Markets: {} of {} (>= {} steps)",
+            summary.active_markets,
+            engine.markets.len(),
+            summary.min_active_steps
+        );
+    }
+    if summary.warmup_steps > 0 {
+        println!("Warm-up: first {} steps excluded from the totals below", summary.warmup_steps);
+    }
+    println!("Total PnL: {:.4}", summary.total_pnl);
+    println!("Step-Weighted PnL: {:.4}", summary.step_weighted_pnl);
+    println!("Step-Weighted Sharpe: {:.2}", summary.step_weighted_sharpe);
+    println!("Total Fills: {}", summary.total_fills);
+    println!("Total Notional: {:.2}", summary.total_notional);
+    println!("Max Drawdown: {:.4}", summary.max_drawdown);
+    println!("Portfolio Max Drawdown: {:.4}", summary.portfolio_max_drawdown);
+    println!("Calmar Ratio: {:.2}", summary.calmar_ratio);
+    println!("Sharpe Ratio: {:.2}", summary.performance.sharpe_ratio);
+    println!("Sortino Ratio: {:.2}", summary.performance.sortino_ratio);
+    println!("Annualized Volatility: {:.4}", summary.performance.annualized_volatility);
 
-    println!("Total PnL: {:.4}", total_pnl);
-    println!("Total Fills: {}", total_fills);
-    println!("Total Notional: {:.2}", total_notional);
-    println!("Max Drawdown: {:.4}", max_dd);
+    logger::write_summary(&summary, path_str(&summary_path)?)?;
+    if normal {
+        println!("\n✅ Summary written to: {}", summary_path.display());
+    }
+
+    // Monte Carlo fan chart over consecutive seeds
+    if let Some(runs) = options.mc_runs.filter(|_| !interrupted) {
+        let seeds: Vec<u64> = (0..runs).map(|i| engine.rng_seed.wrapping_add(i)).collect();
+        let mc_runs = monte_carlo::run_monte_carlo(
+            |seed| engine.fresh_run(markets.clone(), seed),
+            &seeds,
+            total_steps as usize,
+            options.mc_random_inventory,
+        );
+        let curves: Vec<Vec<f64>> = mc_runs.iter().map(|run| run.curve.clone()).collect();
+        let bands = monte_carlo::equity_bands(&curves);
+        logger::write_equity_bands(&bands, path_str(&bands_path)?)?;
+        if normal {
+            println!("✅ Equity bands over {} runs written to: {}", runs, bands_path.display());
+        }
+        
+        // With random starts, show how recovery depends on the starting position
+        if options.mc_random_inventory.is_some() {
+            logger::write_mc_runs(&mc_runs, path_str(&mc_runs_path)?)?;
+            if normal {
+                println!("\n🎲 Final PnL by starting inventory (mean |inventory| / limit):");
+                for bucket in monte_carlo::pnl_by_start_inventory(&mc_runs, 3) {
+                    println!(
+                        "  {:.2}-{:.2}: {} runs, mean final PnL {:.4}",
+                        bucket.min_start, bucket.max_start, bucket.runs, bucket.mean_final_pnl
+                    );
+                }
+                println!("✅ Monte Carlo runs written to: {}", mc_runs_path.display());
+            }
+        }
+    }
 
     Ok(csv_path.to_string_lossy().to_string())
 }
 
 fn main() {
-    match run_demo() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    interrupt::install();
+    match run_demo(&options) {
         Ok(_) => {
-            println!("\n✨ Simulation completed successfully!");
+            if options.verbosity >= Verbosity::Normal {
+                println!("\n✨ Simulation completed successfully!");
+            }
         }
         Err(e) => {
             eprintln!("❌ Error running simulation: {}", e);
@@ -1,28 +1,335 @@
 use crate::market_state::MarketState;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-#[derive(Debug, Clone)]
+/// What `quote` does when the final bid/ask would be crossed or locked;
+/// an uncrossed quote narrower than `min_spread` is always widened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrossedQuotePolicy {
+    /// Re-center symmetrically so the quotes are `min_spread` apart
+    Widen,
+    /// Post nothing this tick
+    Abstain,
+}
+
+/// Price the quote is centered on before skew is applied
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QuoteAnchor {
+    Mid,
+    LastTrade,
+    TradeEwma { alpha: f64 },
+}
+
+/// Whether a taker order priced exactly at our quote crosses it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AtTouchPolicy {
+    Fill,
+    NoFill,
+    // models queue uncertainty: fill with this probability
+    Probabilistic { probability: f64 },
+}
+
+/// How inventory shades the quoted mid
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SkewStrategy {
+    /// `inventory * inventory_skew`
+    Linear,
+    /// PID on the inventory error (target 0): the integral term removes a
+    /// persistent offset the proportional term alone would leave. The
+    /// integral is bounded so its term never shades more than
+    /// `PID_INTEGRAL_SKEW_LIMIT`, which keeps it from winding up while the
+    /// mid is pinned
+    PidSkew { kp: f64, ki: f64, kd: f64 },
+}
+
+/// Imbalance window that tracks the fill rate: the imbalance lookback at
+/// `target_fill_rate` fills per tick, shorter when fills come faster and
+/// longer when they are sparse, within [min_size, max_size]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveWindow {
+    pub min_size: usize,
+    pub max_size: usize,
+    pub target_fill_rate: f64,
+}
+
+/// What happens to a fill whose price is outside `fill_price_range`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FillPriceGuard {
+    /// Fill at the nearest in-range price
+    Clamp,
+    /// Drop the fill
+    Reject,
+}
+
+/// Why a quote came out as it did: each factor's contribution, plus a
+/// readable summary of the ones that moved it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuoteRationale {
+    pub imbalance: f64,
+    pub inventory: f64,
+    pub model_spread: f64, // from the quoting model, widening included
+    pub imbalance_widening: f64,
+    pub inventory_widening: f64,
+    pub toxic_widening: f64,
+    pub settlement_widening: f64,
+    pub spread_clamped_to: Option<f64>, // min_spread or max_spread when binding
+    pub rampup_widening: f64,
+    pub spread: f64,
+    pub skew: f64, // mid shading; positive shades quotes down
+    pub profit_taking: f64,
+    pub size: f64,
+    #[serde(default)]
+    pub imbalance_window: usize, // recent fills the imbalance was summed over
+    #[serde(default)]
+    pub position_limit: f64, // inventory limit the sizes were checked against
+    pub bid_withheld: bool, // zero size at the long limit
+    pub ask_withheld: bool,
+    pub summary: String,
+}
+
+impl QuoteRationale {
+    pub fn describe(&self) -> String {
+        let mut reasons = Vec::new();
+        if self.imbalance_widening > 0.0 {
+            reasons.push(format!(
+                "spread widened {:.4} due to imbalance={:.2}",
+                self.imbalance_widening, self.imbalance
+            ));
+        }
+        if self.inventory_widening > 0.0 {
+            reasons.push(format!(
+                "spread widened {:.4} due to inventory={:.1}",
+                self.inventory_widening, self.inventory
+            ));
+        }
+        if self.toxic_widening > 0.0 {
+            reasons.push(format!("spread widened {:.4} after toxic fills", self.toxic_widening));
+        }
+        if self.settlement_widening > 0.0 {
+            reasons.push(format!("spread widened {:.4} for settlement risk", self.settlement_widening));
+        }
+        if let Some(bound) = self.spread_clamped_to {
+            reasons.push(format!("spread clamped to {:.4}", bound));
+        }
+        if self.rampup_widening > 0.0 {
+            reasons.push(format!("spread widened {:.4} during ramp-up", self.rampup_widening));
+        }
+        if self.skew > 0.0 {
+            reasons.push(format!("quotes skewed down {:.4} due to long inventory={:.1}", self.skew, self.inventory));
+        } else if self.skew < 0.0 {
+            reasons.push(format!("quotes skewed up {:.4} due to short inventory={:.1}", -self.skew, self.inventory));
+        }
+        if self.profit_taking > 0.0 {
+            let side = if self.inventory > 0.0 { "ask" } else { "bid" };
+            reasons.push(format!("{} tightened {:.4} to take profit", side, self.profit_taking));
+        }
+        if self.size < 10.0 {
+            reasons.push(format!("size reduced to {:.1}", self.size));
+        }
+        if self.bid_withheld {
+            reasons.push("bid withheld at the long limit".to_string());
+        }
+        if self.ask_withheld {
+            reasons.push("ask withheld at the short limit".to_string());
+        }
+        if reasons.is_empty() {
+            format!("base quote: spread {:.4}, size {:.1}", self.spread, self.size)
+        } else {
+            reasons.join(", ")
+        }
+    }
+}
+
+/// How quotes are snapped to the `tick_size` grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TickRounding {
+    /// Round bid and ask to the nearest tick separately; the spread can
+    /// move by up to a tick
+    Independent,
+    /// Snap the center to the grid and quote exactly round(spread / tick)
+    /// ticks wide around it
+    PreserveSpread,
+}
+
+/// How a tick's fills interact with the mid moves they cause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchFillMode {
+    /// Each fill moves the mid before the next is recorded (order-dependent)
+    Sequential,
+    /// Every fill is recorded against the pre-tick mid and the combined mid
+    /// move is applied afterwards, so the end-of-step book doesn't depend on
+    /// the order of the batch
+    PreTickMid,
+}
+
+/// Defensive widening after a run of toxic fills (adverse markouts)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ToxicFlowConfig {
+    pub window: usize,       // most recent resolved markouts remembered, 0 disables
+    pub threshold: f64,      // adverse fraction of the window that triggers widening
+    pub widening: f64,       // added to the spread while cooling down
+    pub cooldown_steps: u64, // quotes kept wide after the last trigger
+}
+
+/// How spread and inventory skew are derived
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QuotingModel {
+    /// Imbalance/inventory-widened base spread with `skew_strategy` shading
+    Heuristic,
+    /// Reservation price `mid - q * gamma * sigma^2 * (T - t)` and spread
+    /// `gamma * sigma^2 * (T - t) + (2 / gamma) * ln(1 + gamma / k)`.
+    /// `T` is the resolution step; markets without one use `T - t = 1`.
+    /// `gamma` and `k` must be positive.
+    AvellanedaStoikov { gamma: f64, sigma: f64, k: f64 },
+}
+
+/// Largest mid shade the PID integral term may contribute; half the price
+/// range already pins the shaded mid to its clamp
+pub const PID_INTEGRAL_SKEW_LIMIT: f64 = 0.5;
+
+/// PID controller memory, carried across ticks
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PidState {
+    pub integral: f64,
+    pub prev_error: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketMakerConfig {
     pub window_size: usize,
+    // recent fills summed for the quote's imbalance; None = window_size
+    pub imbalance_lookback: Option<usize>,
+    // fills of imbalance history kept; None = 4x the longest lookback, at
+    // least 100
+    pub flow_window: Option<usize>,
     pub base_spread: f64,
     pub min_spread: f64,
     pub max_spread: f64,
     pub inventory_skew: f64,
+    pub crossed_quote_policy: CrossedQuotePolicy,
+    pub max_fills_per_step: Option<usize>, // None = unlimited
+    // Force an unwind after this many consecutive ticks pinned at the
+    // inventory limit (None = never escalate)
+    pub stuck_limit_steps: Option<u64>,
+    pub stuck_limit_epsilon: f64,
+    pub quote_anchor: QuoteAnchor,
+    // skew multiplier grows as 1 + coeff / (1 + steps left to resolution)
+    pub resolution_urgency: f64,
+    pub at_touch_policy: AtTouchPolicy,
+    // tighten the exit side by coeff * per-unit unrealized gain
+    pub profit_taking_coeff: f64,
+    pub skew_strategy: SkewStrategy,
+    pub toxic_flow: Option<ToxicFlowConfig>, // None = no defensive widening
+    pub batch_fill_mode: BatchFillMode,
+    // fills at 0 or 1 carry no notional and degenerate the PnL math
+    pub fill_price_range: (f64, f64),
+    pub fill_price_guard: FillPriceGuard,
+    pub adaptive_window: Option<AdaptiveWindow>, // None = fixed window_size
+    // over a market's first N fills quotes start at max_spread and minimum
+    // size, relaxing linearly to normal (0 = no ramp-up)
+    pub rampup_fills: u64,
+    pub quoting_model: QuotingModel,
+    // extra spread coeff * p * (1 - p), the variance of the binary payout,
+    // weighted by proximity to resolution
+    pub settlement_risk_coeff: f64,
+    // quote depth: levels step out one inside spread apiece, each showing
+    // ladder_size_decay times the size of the level inside it
+    pub ladder_levels: usize,
+    pub ladder_size_decay: f64,
+    pub tick_size: Option<f64>, // None = continuous prices
+    pub tick_rounding: TickRounding,
+    // takers walk the book: fills move price_impact per unit of size against them
+    pub price_impact: f64,
 }
 
 impl Default for MarketMakerConfig {
     fn default() -> Self {
         MarketMakerConfig {
             window_size: 20,
+            imbalance_lookback: None,
+            flow_window: None,
             base_spread: 0.05,
             min_spread: 0.01,
             max_spread: 0.5,
             inventory_skew: 0.001,
+            crossed_quote_policy: CrossedQuotePolicy::Widen,
+            max_fills_per_step: None,
+            stuck_limit_steps: None,
+            stuck_limit_epsilon: 1.0,
+            quote_anchor: QuoteAnchor::Mid,
+            resolution_urgency: 0.0,
+            at_touch_policy: AtTouchPolicy::Fill,
+            profit_taking_coeff: 0.0,
+            skew_strategy: SkewStrategy::Linear,
+            toxic_flow: None,
+            batch_fill_mode: BatchFillMode::Sequential,
+            fill_price_range: (0.001, 0.999),
+            fill_price_guard: FillPriceGuard::Clamp,
+            adaptive_window: None,
+            rampup_fills: 0,
+            quoting_model: QuotingModel::Heuristic,
+            settlement_risk_coeff: 0.0,
+            ladder_levels: 1,
+            ladder_size_decay: 0.5,
+            tick_size: None,
+            tick_rounding: TickRounding::Independent,
+            price_impact: 0.0,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// The config a step's quotes were produced under, with the values the
+/// adaptive logic derived from it that tick. The derived values are None
+/// when the maker abstained or was only unwinding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    pub config: MarketMakerConfig,
+    pub spread: Option<f64>, // after widening, clamps and ramp-up
+    pub skew: Option<f64>,
+    pub inventory_limit: Option<f64>,
+    pub imbalance_window: Option<usize>,
+}
+
+impl EffectiveConfig {
+    pub fn new(config: &MarketMakerConfig, why: Option<&QuoteRationale>) -> Self {
+        EffectiveConfig {
+            config: config.clone(),
+            spread: why.map(|why| why.spread),
+            skew: why.map(|why| why.skew),
+            inventory_limit: why.map(|why| why.position_limit),
+            imbalance_window: why.map(|why| why.imbalance_window),
+        }
+    }
+}
+
+/// How often quotes hit each bound; a counter that climbs every tick means
+/// the config is saturating that bound
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClampCounters {
+    pub min_spread: u64,
+    pub max_spread: u64,
+    pub mid_shade: u64, // skewed mid pinned to [0.01, 0.99]
+    pub bid_floor: u64,
+    pub ask_ceiling: u64,
+    pub crossed: u64, // bid >= ask caught by the final guard
+    #[serde(default)]
+    pub fill_price: u64, // fills priced outside fill_price_range
+}
+
+/// Quote-to-fill conversion: where potential trades are lost between
+/// posting a quote and filling an order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunnelCounters {
+    pub quotes_posted: u64,
+    pub orders_seen: u64,
+    pub orders_crossed: u64, // crossed our quote, before the fill cap
+    pub orders_filled: u64,
+    pub volume_filled: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Order {
     pub side: String,
     pub size: f64,
@@ -36,9 +343,82 @@ pub struct FillResult {
     pub price: f64,
 }
 
+/// Two-sided quote; a side with zero size is not offered
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub bid: f64,
+    pub ask: f64,
+    pub bid_size: f64,
+    pub ask_size: f64,
+}
+
+/// One price level of our resting quotes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Our resting quotes, inside level first on each side. Reposted from the
+/// ladder every tick and depleted by taker fills, so a partially filled
+/// level shows what is left of it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+impl OrderBook {
+    /// Requote: replace every level with the ladder's prices and sizes
+    pub fn post(&mut self, ladder: &[Quote]) {
+        self.bids.clear();
+        self.asks.clear();
+        for level in ladder {
+            self.bids.push(BookLevel { price: level.bid, size: level.bid_size });
+            self.asks.push(BookLevel { price: level.ask, size: level.ask_size });
+        }
+    }
+
+    pub fn bid_depth(&self) -> f64 {
+        self.bids.iter().map(|level| level.size).sum()
+    }
+
+    pub fn ask_depth(&self) -> f64 {
+        self.asks.iter().map(|level| level.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketMaker {
     pub config: MarketMakerConfig,
     pub imbalance_window: VecDeque<f64>,
+    pub steps_at_limit: u64,
+    pub stuck: bool,
+    pub trade_ewma: Option<f64>,
+    pub time: u64, // engine clock for the current tick
+    pub rng: ChaCha8Rng,
+    #[serde(default)]
+    pub clamps: ClampCounters,
+    #[serde(default)]
+    pub pid: PidState,
+    // recent fill outcomes, true when the markout went against us
+    #[serde(default)]
+    pub adverse_fills: VecDeque<bool>,
+    #[serde(default)]
+    pub toxic_cooldown: u64,
+    #[serde(default)]
+    pub funnel: FunnelCounters,
+    // EWMA of fills per tick, driving the adaptive window
+    #[serde(default)]
+    pub fill_rate: Option<f64>,
+    // build a QuoteRationale on every quote
+    #[serde(default)]
+    pub explain: bool,
+    #[serde(skip)]
+    pub rationale: Option<QuoteRationale>,
+    // what is left of this tick's quotes after taker flow
+    #[serde(default)]
+    pub book: OrderBook,
 }
 
 impl MarketMaker {
@@ -46,55 +426,434 @@ impl MarketMaker {
         let mut cfg = config.unwrap_or_default();
         cfg.base_spread = state.spread;
         
+        // Separate stream from the market's order flow so fill decisions
+        // never perturb the flow sequence
+        let mut rng = ChaCha8Rng::seed_from_u64(state.rng_seed.unwrap_or(0));
+        rng.set_stream(1);
+        
         MarketMaker {
             config: cfg,
             imbalance_window: VecDeque::new(),
+            steps_at_limit: 0,
+            stuck: false,
+            trade_ewma: None,
+            time: 0,
+            rng,
+            clamps: ClampCounters::default(),
+            pid: PidState::default(),
+            adverse_fills: VecDeque::new(),
+            toxic_cooldown: 0,
+            funnel: FunnelCounters::default(),
+            fill_rate: None,
+            explain: false,
+            rationale: None,
+            book: OrderBook::default(),
         }
     }
 
     /// Generate bid/ask quotes based on current market state
-    /// Returns (bid, ask, size)
-    pub fn quote(&mut self, state: &mut MarketState) -> (f64, f64, f64) {
-        let mid = state.mid;
+    /// Returns None if the maker abstains this tick. At the inventory or
+    /// exposure limit the side that would grow the position is quoted with
+    /// zero size.
+    pub fn quote(&mut self, state: &mut MarketState) -> Option<Quote> {
+        self.rationale = None;
+        if state.halted {
+            return self.unwind_quote(state);
+        }
+        // Anchor falls back to mid until there are trades to anchor on
+        let mid = match self.config.quote_anchor {
+            QuoteAnchor::Mid => state.mid,
+            QuoteAnchor::LastTrade => state.last_trade_price.unwrap_or(state.mid),
+            QuoteAnchor::TradeEwma { .. } => self.trade_ewma.unwrap_or(state.mid),
+        };
         
         // Calculate imbalance from recent window
         let imbalance: f64 = self.imbalance_window
             .iter()
             .rev()
-            .take(self.config.window_size)
+            .take(self.effective_window())
             .sum();
         
         let abs_imb = imbalance.abs();
+        let mut why = QuoteRationale {
+            imbalance,
+            inventory: state.inventory,
+            imbalance_window: self.effective_window(),
+            ..QuoteRationale::default()
+        };
         
-        // Adaptive spread: widens with imbalance and inventory
-        let spread = self.config.base_spread 
-            * (1.0 + abs_imb / 10.0 + state.inventory.abs() * self.config.inventory_skew);
-        let spread = spread.max(self.config.min_spread).min(self.config.max_spread);
+        let (spread, skew) = match self.config.quoting_model {
+            QuotingModel::Heuristic => {
+                // Adaptive spread: widens with imbalance and inventory
+                why.imbalance_widening = self.config.base_spread * abs_imb / 10.0;
+                why.inventory_widening = self.config.base_spread * state.inventory.abs() * self.config.inventory_skew;
+                let spread = self.config.base_spread
+                    * (1.0 + abs_imb / 10.0 + state.inventory.abs() * self.config.inventory_skew);
+                // Inventory skew: shade mid price based on inventory
+                (spread, self.inventory_skew(state.inventory) * self.urgency_factor(state))
+            }
+            QuotingModel::AvellanedaStoikov { gamma, sigma, k } => {
+                let risk = gamma * sigma * sigma * self.time_to_horizon(state);
+                (risk + (2.0 / gamma) * (1.0 + gamma / k).ln(), state.inventory * risk)
+            }
+        };
+        why.model_spread = spread;
+        why.toxic_widening = self.toxic_widening();
+        why.settlement_widening = self.settlement_risk_spread(state, mid);
+        let spread = spread + why.toxic_widening + why.settlement_widening;
+        if spread < self.config.min_spread {
+            self.clamps.min_spread += 1;
+            why.spread_clamped_to = Some(self.config.min_spread);
+        } else if spread > self.config.max_spread {
+            self.clamps.max_spread += 1;
+            why.spread_clamped_to = Some(self.config.max_spread);
+        }
+        let spread = spread.clamp(self.config.min_spread, self.config.max_spread);
+        let ramp = self.rampup_progress(state);
+        why.rampup_widening = (self.config.max_spread - spread).max(0.0) * (1.0 - ramp);
+        let spread = spread + why.rampup_widening;
+        why.spread = spread;
+        why.skew = skew;
         
-        // Inventory skew: shade mid price based on inventory
-        let skew = state.inventory * self.config.inventory_skew;
-        let mid_shaded = (mid - skew).max(0.01).min(0.99);
+        let raw_mid = mid - skew;
+        if !(0.01..=0.99).contains(&raw_mid) {
+            self.clamps.mid_shade += 1;
+        }
+        let mid_shaded = raw_mid.clamp(0.01, 0.99);
         
         // Calculate bid/ask
-        let bid = (mid_shaded - spread / 2.0).max(0.0);
-        let ask = (mid_shaded + spread / 2.0).min(1.0);
+        if mid_shaded - spread / 2.0 < 0.0 {
+            self.clamps.bid_floor += 1;
+        }
+        if mid_shaded + spread / 2.0 > 1.0 {
+            self.clamps.ask_ceiling += 1;
+        }
+        let mut bid = (mid_shaded - spread / 2.0).max(0.0);
+        let mut ask = (mid_shaded + spread / 2.0).min(1.0);
+        
+        // Profit taking: lean the exit side toward mid to monetize a
+        // favorable move, never past the shaded mid
+        if self.config.profit_taking_coeff > 0.0 && state.inventory != 0.0 {
+            let gain_per_unit = state.unrealized_pnl() / state.inventory.abs();
+            if gain_per_unit > 0.0 {
+                let tighten = (self.config.profit_taking_coeff * gain_per_unit).min(spread / 2.0);
+                why.profit_taking = tighten;
+                if state.inventory > 0.0 {
+                    ask -= tighten;
+                } else {
+                    bid += tighten;
+                }
+            }
+        }
+        
+        let (bid, ask) = self.snap_to_ticks(bid, ask, spread);
         
         // Size inversely related to inventory
-        let size = (10.0 - state.inventory.abs() / 10.0).max(1.0).min(20.0);
+        let size = ((10.0 - state.inventory.abs() / 10.0) * ramp).clamp(1.0, 20.0);
         
         // Update state spread
-        state.spread = spread;
+        state.record_quote(spread);
+        
+        let (bid, ask) = self.enforce_uncrossed(bid, ask)?;
+        debug_assert!(
+            bid < ask && ask - bid >= self.min_quote_gap() - 1e-9,
+            "quote narrower than min_spread: bid {} ask {}",
+            bid,
+            ask
+        );
         
-        (bid, ask, size)
+        let limit = state.max_position();
+        let quote = Quote {
+            bid,
+            ask,
+            bid_size: if state.inventory >= limit { 0.0 } else { size },
+            ask_size: if state.inventory <= -limit { 0.0 } else { size },
+        };
+        if self.explain {
+            why.size = size;
+            why.position_limit = limit;
+            why.bid_withheld = quote.bid_size == 0.0;
+            why.ask_withheld = quote.ask_size == 0.0;
+            why.summary = why.describe();
+            self.rationale = Some(why);
+        }
+        Some(quote)
+    }
+
+    /// Quote for a market halted at its drawdown limit: only the side that
+    /// reduces inventory, at the mid, sized to the whole position so takers
+    /// can flatten it but never flip it. None once flat
+    fn unwind_quote(&self, state: &MarketState) -> Option<Quote> {
+        let width = self.config.min_spread;
+        let mid = state.mid;
+        if state.inventory > 0.0 {
+            Some(Quote { bid: mid - width, ask: mid, bid_size: 0.0, ask_size: state.inventory })
+        } else if state.inventory < 0.0 {
+            Some(Quote { bid: mid, ask: mid + width, bid_size: -state.inventory, ask_size: 0.0 })
+        } else {
+            None
+        }
+    }
+
+    /// 0 for a market with no fills, rising to 1 once `rampup_fills` fills
+    /// of history exist
+    fn rampup_progress(&self, state: &MarketState) -> f64 {
+        if self.config.rampup_fills == 0 {
+            return 1.0;
+        }
+        (state.fill_count as f64 / self.config.rampup_fills as f64).min(1.0)
+    }
+
+    /// Number of recent fills the imbalance is summed over
+    pub fn effective_window(&self) -> usize {
+        match (self.config.adaptive_window, self.fill_rate) {
+            (Some(adaptive), Some(rate)) if rate > 0.0 => {
+                let scaled = self.imbalance_lookback() as f64 * adaptive.target_fill_rate / rate;
+                (scaled.round() as usize).clamp(adaptive.min_size, adaptive.max_size.max(adaptive.min_size))
+            }
+            (Some(adaptive), _) => adaptive.max_size.max(adaptive.min_size),
+            (None, _) => self.imbalance_lookback(),
+        }
+    }
+
+    /// Base number of recent fills the quote's imbalance sums
+    pub fn imbalance_lookback(&self) -> usize {
+        self.config.imbalance_lookback.unwrap_or(self.config.window_size)
+    }
+
+    /// Number of fills of imbalance history kept
+    pub fn flow_window(&self) -> usize {
+        self.config.flow_window.unwrap_or_else(|| {
+            let longest = match self.config.adaptive_window {
+                Some(adaptive) => self.imbalance_lookback().max(adaptive.max_size),
+                None => self.imbalance_lookback(),
+            };
+            (longest * 4).max(100)
+        })
+    }
+
+    fn update_fill_rate(&mut self, fills: usize) {
+        const ALPHA: f64 = 0.1;
+        let fills = fills as f64;
+        self.fill_rate = Some(match self.fill_rate {
+            Some(rate) => ALPHA * fills + (1.0 - ALPHA) * rate,
+            None => fills,
+        });
+    }
+
+    /// Mid shade for the current inventory; the PID strategy advances its
+    /// controller state, so this is called once per quote
+    fn inventory_skew(&mut self, inventory: f64) -> f64 {
+        match self.config.skew_strategy {
+            SkewStrategy::Linear => inventory * self.config.inventory_skew,
+            SkewStrategy::PidSkew { kp, ki, kd } => {
+                let error = inventory;
+                self.pid.integral += error;
+                if ki > 0.0 {
+                    let bound = PID_INTEGRAL_SKEW_LIMIT / ki;
+                    self.pid.integral = self.pid.integral.clamp(-bound, bound);
+                }
+                let derivative = self.pid.prev_error.map_or(0.0, |prev| error - prev);
+                self.pid.prev_error = Some(error);
+                kp * error + ki * self.pid.integral + kd * derivative
+            }
+        }
+    }
+
+    /// Steps left until resolution (`T - t`), or 1 for markets without one
+    fn time_to_horizon(&self, state: &MarketState) -> f64 {
+        match state.resolution_step {
+            Some(step) => step.saturating_sub(self.time) as f64,
+            None => 1.0,
+        }
+    }
+
+    /// Spread charged for the jump to 0/1 at settlement: largest near 0.5
+    /// and as resolution nears; markets with no resolution step pay in full
+    fn settlement_risk_spread(&self, state: &MarketState, mid: f64) -> f64 {
+        let proximity = match state.resolution_step {
+            Some(resolution) => 1.0 / (1.0 + resolution.saturating_sub(self.time) as f64),
+            None => 1.0,
+        };
+        self.config.settlement_risk_coeff * mid * (1.0 - mid) * proximity
+    }
+
+    /// Skew multiplier for markets approaching resolution: carrying
+    /// inventory into the jump to 0/1 gets riskier as the deadline nears
+    fn urgency_factor(&self, state: &MarketState) -> f64 {
+        match state.resolution_step {
+            Some(resolution) if self.config.resolution_urgency > 0.0 => {
+                let remaining = resolution.saturating_sub(self.time) as f64;
+                1.0 + self.config.resolution_urgency / (1.0 + remaining)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Remember whether a resolved fill was toxic and start the cooldown
+    /// once adverse fills dominate the window
+    pub fn record_markout(&mut self, markout: f64) {
+        let Some(toxic) = self.config.toxic_flow.filter(|toxic| toxic.window > 0) else { return };
+        self.adverse_fills.push_back(markout < 0.0);
+        while self.adverse_fills.len() > toxic.window {
+            self.adverse_fills.pop_front();
+        }
+
+        let adverse = self.adverse_fills.iter().filter(|&&a| a).count();
+        if self.adverse_fills.len() == toxic.window
+            && adverse as f64 >= toxic.threshold * toxic.window as f64
+        {
+            self.toxic_cooldown = toxic.cooldown_steps;
+        }
+    }
+
+    /// Extra spread while cooling down from toxic flow; one quote per tick
+    /// burns one step of cooldown
+    fn toxic_widening(&mut self) -> f64 {
+        match self.config.toxic_flow {
+            Some(toxic) if self.toxic_cooldown > 0 => {
+                self.toxic_cooldown -= 1;
+                toxic.widening
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Quote `levels` deep: the inside quote followed by levels stepping
+    /// away from mid. Empty if the maker abstains this tick.
+    pub fn quote_ladder(&mut self, state: &mut MarketState, levels: usize) -> Vec<Quote> {
+        match self.quote(state) {
+            Some(inside) => self.ladder(inside, levels),
+            None => Vec::new(),
+        }
+    }
+
+    /// Stack levels outward from `inside`, each one inside spread further
+    /// from mid with `ladder_size_decay` times the size of the last. A side
+    /// that would price outside (0, 1) is shown with zero size, and the
+    /// ladder stops once both sides have.
+    pub fn ladder(&self, inside: Quote, levels: usize) -> Vec<Quote> {
+        let mut ladder = Vec::with_capacity(levels.max(1));
+        self.ladder_into(inside, levels, &mut ladder);
+        ladder
+    }
+
+    /// `ladder` into a caller-owned buffer, cleared first, so the engine
+    /// can reuse one across ticks
+    pub fn ladder_into(&self, inside: Quote, levels: usize, ladder: &mut Vec<Quote>) {
+        ladder.clear();
+        let step = inside.ask - inside.bid;
+        ladder.push(inside);
+        for level in 1..levels {
+            let offset = step * level as f64;
+            let decay = self.config.ladder_size_decay.powi(level as i32);
+            let (bid, ask) = (inside.bid - offset, inside.ask + offset);
+            if bid <= 0.0 && ask >= 1.0 {
+                break;
+            }
+            ladder.push(Quote {
+                bid: bid.max(0.0),
+                ask: ask.min(1.0),
+                bid_size: if bid > 0.0 { inside.bid_size * decay } else { 0.0 },
+                ask_size: if ask < 1.0 { inside.ask_size * decay } else { 0.0 },
+            });
+        }
+    }
+
+    /// Snap quote prices to the tick grid, if one is configured. A
+    /// spread-preserving quote is shifted whole to stay within [0, 1].
+    fn snap_to_ticks(&self, bid: f64, ask: f64, spread: f64) -> (f64, f64) {
+        let Some(tick) = self.tick_size() else {
+            return (bid, ask);
+        };
+        match self.config.tick_rounding {
+            TickRounding::Independent => {
+                // The top tick may sit below 1.0 when 1 / tick isn't whole
+                let top = (1.0 / tick).floor();
+                let snap = |price: f64| (price / tick).round().clamp(0.0, top) * tick;
+                (snap(bid), snap(ask))
+            }
+            TickRounding::PreserveSpread => {
+                // Work in whole ticks so the width is exact
+                let top = (1.0 / tick).floor();
+                let ticks = (spread / tick).round().clamp(1.0, top.max(1.0));
+                let center = ((bid + ask) / 2.0 / tick).round();
+                let low = (center - (ticks / 2.0).floor()).clamp(0.0, (top - ticks).max(0.0));
+                (low * tick, (low + ticks) * tick)
+            }
+        }
+    }
+
+    fn tick_size(&self) -> Option<f64> {
+        self.config.tick_size.filter(|tick| *tick > 0.0)
+    }
+
+    /// Final guard on the quote: ask must sit at least `min_spread` above
+    /// bid. A narrow quote is re-centered that wide, in whole ticks when a
+    /// tick size is set; a crossed or locked one goes by the crossed-quote
+    /// policy
+    fn enforce_uncrossed(&mut self, bid: f64, ask: f64) -> Option<(f64, f64)> {
+        // Keep the gap positive even if min_spread is misconfigured
+        let gap = self.min_quote_gap();
+        // Slack so a quote exactly min_spread wide passes despite rounding;
+        // the slack alone must never let a locked quote through
+        if bid < ask && ask - bid >= gap - 1e-12 {
+            return Some((bid, ask));
+        }
+        if bid >= ask {
+            self.clamps.crossed += 1;
+            if self.config.crossed_quote_policy == CrossedQuotePolicy::Abstain {
+                return None;
+            }
+        }
+        let center = (bid + ask) / 2.0;
+        match self.tick_size() {
+            Some(tick) => {
+                let ticks = (gap / tick - 1e-9).ceil().max(1.0);
+                let top = (1.0 / tick).floor();
+                let low = ((center / tick).round() - (ticks / 2.0).floor()).clamp(0.0, (top - ticks).max(0.0));
+                Some((low * tick, (low + ticks).min(top) * tick))
+            }
+            None => {
+                let center = center.clamp(gap / 2.0, 1.0 - gap / 2.0);
+                Some((center - gap / 2.0, center + gap / 2.0))
+            }
+        }
+    }
+
+    fn min_quote_gap(&self) -> f64 {
+        self.config.min_spread.clamp(f64::EPSILON, 1.0)
     }
 
     /// Process a fill and update internal state
     pub fn on_fill(&mut self, state: &mut MarketState, side: &str, size: f64) {
+        let adjustment = self.fill_mid_adjustment(side, size);
+        state.mid = (state.mid + adjustment).clamp(0.01, 0.99);
+        
+        // Defensive adjustment when inventory is high
+        let correction = Self::defensive_correction(state);
+        state.mid = (state.mid + correction).clamp(0.01, 0.99);
+    }
+
+    /// Process a whole tick's fills after they were all recorded at the
+    /// pre-tick mid; every step here is a sum over the batch
+    fn on_fill_batch(&mut self, state: &mut MarketState, fills: &[FillResult]) {
+        let adjustment: f64 = fills
+            .iter()
+            .map(|fill| self.fill_mid_adjustment(&fill.side, fill.size))
+            .sum();
+        let correction = Self::defensive_correction(state) * fills.len() as f64;
+        state.mid = (state.mid + adjustment + correction).clamp(0.01, 0.99);
+    }
+
+    /// Record the fill in the imbalance window and return the mid move its
+    /// flow causes
+    fn fill_mid_adjustment(&mut self, side: &str, size: f64) -> f64 {
         let delta = if side == "buy" { size } else { -size };
         
         // Update imbalance window
         self.imbalance_window.push_back(delta);
-        let max_window = (self.config.window_size * 4).max(100);
+        let max_window = self.flow_window();
         while self.imbalance_window.len() > max_window {
             self.imbalance_window.pop_front();
         }
@@ -102,50 +861,871 @@ impl MarketMaker {
         // Adjust mid based on flow
         let alpha = 0.05;
         let flow = delta;
-        let mid_adjustment = alpha * (flow / (10.0 + flow.abs()));
-        state.mid = (state.mid + mid_adjustment).max(0.01).min(0.99);
-        
-        // Defensive adjustment when inventory is high
+        alpha * (flow / (10.0 + flow.abs()))
+    }
+
+    /// Push the mid against inventory once it is above 80% of the limit
+    fn defensive_correction(state: &MarketState) -> f64 {
         let inv = state.inventory;
-        if inv.abs() > state.inventory_limit * 0.8 {
-            let correction = if inv > 0.0 { -0.05 } else { 0.05 };
-            state.mid = (state.mid + correction).max(0.01).min(0.99);
+        if inv.abs() > state.effective_inventory_limit() * 0.8 {
+            if inv > 0.0 { -0.05 } else { 0.05 }
+        } else {
+            0.0
         }
     }
 
-    /// Process incoming market orders and generate fills
-    pub fn on_tick(&mut self, state: &mut MarketState, market_order_flow: &[Order]) -> Vec<FillResult> {
+    /// Process incoming market orders against this tick's quote, as produced
+    /// by `quote` (and possibly reconciled across an outcome group since)
+    pub fn on_tick(
+        &mut self,
+        state: &mut MarketState,
+        market_order_flow: &[Order],
+        ladder: &[Quote],
+    ) -> Vec<FillResult> {
         let mut fills = Vec::new();
-        let (bid, ask, _size) = self.quote(state);
+        self.funnel.orders_seen += market_order_flow.len() as u64;
+        self.book.post(ladder);
+        let Some(&inside) = ladder.first() else { return fills };
+        let (bid, ask) = (inside.bid, inside.ask);
+        let size = inside.bid_size.max(inside.ask_size);
+        self.funnel.quotes_posted += 1;
         
+        // Fills are truncated to the room left under the inventory and
+        // exposure limits, so a burst of same-side orders can't carry the
+        // position past either
+        let limit = state.max_position();
+        let mut inventory = state.inventory;
+        // Quoted size rests in the book per level and side this tick; orders
+        // walk it from the inside out, depleting levels as they fill, and
+        // what no level can absorb walks away
+        let mut book = std::mem::take(&mut self.book);
         for order in market_order_flow {
-            match order.side.as_str() {
-                "buy" if order.price >= ask => {
-                    // Taker buys, we sell
-                    fills.push(FillResult {
-                        side: "sell".to_string(),
-                        size: order.size,
-                        price: ask,
-                    });
+            let (side, levels) = match order.side.as_str() {
+                // Taker buys, we sell
+                "buy" => ("sell", &mut book.asks),
+                // Taker sells, we buy
+                "sell" => ("buy", &mut book.bids),
+                _ => continue,
+            };
+            let mut order_left = order.size;
+            let mut crossed = false;
+            let mut filled = false;
+            for level in levels.iter_mut() {
+                let price = level.price;
+                let room = if side == "sell" { limit + inventory } else { limit - inventory };
+                let through = if side == "sell" { order.price > price } else { order.price < price };
+                if !self.crosses(order.price, price, through) {
+                    break;
+                }
+                crossed = true;
+                
+                // Crossing orders past the cap are rejected for this tick
+                if self.config.max_fills_per_step.is_some_and(|cap| fills.len() >= cap) {
+                    break;
+                }
+                if order_left <= 0.0 || room <= 0.0 {
+                    break;
                 }
-                "sell" if order.price <= bid => {
-                    // Taker sells, we buy
-                    fills.push(FillResult {
-                        side: "buy".to_string(),
-                        size: order.size,
-                        price: bid,
-                    });
+                if level.size <= 0.0 {
+                    continue;
                 }
-                _ => {}
+                let fill_size = order_left.min(level.size).min(room);
+                let Some(price) = self.guard_fill_price(self.impacted_price(side, price, fill_size)) else { continue };
+                level.size -= fill_size;
+                order_left -= fill_size;
+                inventory += if side == "buy" { fill_size } else { -fill_size };
+                filled = true;
+                self.funnel.volume_filled += fill_size;
+                fills.push(FillResult {
+                    side: side.to_string(),
+                    size: fill_size,
+                    price,
+                });
+            }
+            if crossed {
+                self.funnel.orders_crossed += 1;
+            }
+            if filled {
+                self.funnel.orders_filled += 1;
             }
         }
+        self.book = book;
+        
+        // Pinned at the limit with no offsetting flow: cross the spread to unwind
+        if self.update_stuck(state) {
+            // Unwind far enough to clear the detection band by one quote size
+            let target = (state.effective_inventory_limit() - self.config.stuck_limit_epsilon - size).max(0.0);
+            let unwind = (state.inventory.abs() - target).max(0.0);
+            let (side, price) = if state.inventory > 0.0 { ("sell", bid) } else { ("buy", ask) };
+            if let Some(price) = self.guard_fill_price(price) {
+                fills.push(FillResult {
+                    side: side.to_string(),
+                    size: unwind,
+                    price,
+                });
+            }
+        }
+        
+        self.update_fill_rate(fills.len());
         
         // Record fills and update state
-        for fill in &fills {
-            state.record_fill(&fill.side, fill.size, fill.price);
-            self.on_fill(state, &fill.side, fill.size);
+        match self.config.batch_fill_mode {
+            BatchFillMode::Sequential => {
+                for fill in &fills {
+                    let mid = state.mid;
+                    state.record_fill(&fill.side, fill.size, fill.price, mid, self.time);
+                    self.update_trade_ewma(fill.price);
+                    self.on_fill(state, &fill.side, fill.size);
+                }
+            }
+            BatchFillMode::PreTickMid => {
+                let mid = state.mid;
+                for fill in &fills {
+                    state.record_fill(&fill.side, fill.size, fill.price, mid, self.time);
+                }
+                // One EWMA update at the size-weighted price keeps it order-free
+                let volume: f64 = fills.iter().map(|f| f.size).sum();
+                if volume > 0.0 {
+                    let vwap = fills.iter().map(|f| f.size * f.price).sum::<f64>() / volume;
+                    self.update_trade_ewma(vwap);
+                }
+                self.on_fill_batch(state, &fills);
+            }
         }
         
         fills
     }
+
+    /// Price a fill of `size` after depth consumption: a taker buy (our
+    /// sell) pays up from the ask, a taker sell gives up from the bid
+    pub fn impacted_price(&self, side: &str, price: f64, size: f64) -> f64 {
+        let impact = self.config.price_impact * size;
+        if side == "sell" {
+            (price + impact).min(1.0)
+        } else {
+            (price - impact).max(0.0)
+        }
+    }
+
+    /// Apply the fill price guard; None when the fill is rejected
+    fn guard_fill_price(&mut self, price: f64) -> Option<f64> {
+        let (low, high) = self.config.fill_price_range;
+        if (low..=high).contains(&price) {
+            return Some(price);
+        }
+        self.clamps.fill_price += 1;
+        
+        match self.config.fill_price_guard {
+            FillPriceGuard::Clamp => Some(price.clamp(low, high)),
+            FillPriceGuard::Reject => None,
+        }
+    }
+
+    /// Decide whether a taker order crosses our quote, applying the
+    /// at-touch policy when its price sits exactly on the quote
+    fn crosses(&mut self, order_price: f64, quote_price: f64, through: bool) -> bool {
+        if through {
+            return true;
+        }
+        if order_price != quote_price {
+            return false;
+        }
+        
+        match self.config.at_touch_policy {
+            AtTouchPolicy::Fill => true,
+            AtTouchPolicy::NoFill => false,
+            AtTouchPolicy::Probabilistic { probability } => self.rng.gen::<f64>() < probability,
+        }
+    }
+
+    /// Track consecutive ticks spent within epsilon of the inventory limit
+    /// and report whether the maker should force an unwind
+    fn update_stuck(&mut self, state: &MarketState) -> bool {
+        let threshold = state.effective_inventory_limit() - self.config.stuck_limit_epsilon;
+        if state.inventory.abs() >= threshold {
+            self.steps_at_limit += 1;
+        } else {
+            self.steps_at_limit = 0;
+        }
+        
+        self.stuck = match self.config.stuck_limit_steps {
+            Some(max_steps) => self.steps_at_limit > max_steps,
+            None => false,
+        };
+        self.stuck
+    }
+
+    fn update_trade_ewma(&mut self, price: f64) {
+        let alpha = match self.config.quote_anchor {
+            QuoteAnchor::TradeEwma { alpha } => alpha,
+            _ => return,
+        };
+        self.trade_ewma = Some(match self.trade_ewma {
+            Some(ewma) => alpha * price + (1.0 - alpha) * ewma,
+            None => price,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maker(state: &MarketState, config: MarketMakerConfig) -> MarketMaker {
+        MarketMaker::new(state, Some(config))
+    }
+
+    fn on_grid(price: f64, tick: f64) -> bool {
+        let ticks = price / tick;
+        (ticks - ticks.round()).abs() < 1e-6
+    }
+
+    #[test]
+    fn quotes_are_never_crossed_over_random_configs() {
+        let mut rng = ChaCha8Rng::seed_from_u64(201);
+        for _ in 0..5000 {
+            let mut state = MarketState::new("fuzz", rng.gen_range(0.0..=1.0));
+            state.spread = rng.gen_range(0.0..0.5);
+            state.inventory = rng.gen_range(-500.0..=500.0);
+            state.inventory_limit = rng.gen_range(0.0..300.0);
+            state.last_trade_price = Some(rng.gen_range(0.0..=1.0));
+
+            // clamp needs min_spread <= max_spread; anything else is fair game
+            let min_spread = rng.gen_range(-0.05..0.2);
+            let config = MarketMakerConfig {
+                min_spread,
+                max_spread: min_spread + rng.gen_range(0.0..1.0),
+                inventory_skew: rng.gen_range(0.0..0.05),
+                crossed_quote_policy: if rng.gen() { CrossedQuotePolicy::Widen } else { CrossedQuotePolicy::Abstain },
+                quote_anchor: if rng.gen() { QuoteAnchor::Mid } else { QuoteAnchor::LastTrade },
+                profit_taking_coeff: rng.gen_range(0.0..5.0),
+                tick_size: rng.gen::<bool>().then(|| rng.gen_range(0.001..0.2)),
+                tick_rounding: if rng.gen() { TickRounding::Independent } else { TickRounding::PreserveSpread },
+                ..MarketMakerConfig::default()
+            };
+            let mut mm = maker(&state, config);
+            for _ in 0..rng.gen_range(0..30) {
+                mm.imbalance_window.push_back(rng.gen_range(-30.0..30.0));
+            }
+
+            if let Some(quote) = mm.quote(&mut state) {
+                assert!(quote.bid < quote.ask, "crossed quote {:?} from {:?}", quote, mm.config);
+                assert!(quote.bid >= 0.0 && quote.ask <= 1.0, "quote {:?} outside [0, 1]", quote);
+                if let Some(tick) = mm.config.tick_size {
+                    assert!(on_grid(quote.bid, tick) && on_grid(quote.ask, tick), "off-grid quote {:?} for tick {}", quote, tick);
+                }
+            }
+        }
+    }
+
+    fn crossing_orders(n: usize) -> Vec<Order> {
+        (0..n)
+            .map(|i| Order {
+                side: if i % 2 == 0 { "buy" } else { "sell" }.to_string(),
+                size: 1.0,
+                price: if i % 2 == 0 { 1.0 } else { 0.0 },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fill_cap_limits_fills_per_tick() {
+        let mut state = MarketState::new("capped", 0.5);
+        let config = MarketMakerConfig {
+            max_fills_per_step: Some(2),
+            ..MarketMakerConfig::default()
+        };
+        let mut mm = maker(&state, config);
+        let quote = mm.quote(&mut state).expect("a two-sided quote");
+
+        let fills = mm.on_tick(&mut state, &crossing_orders(10), &[quote]);
+        assert_eq!(fills.len(), 2);
+        assert_eq!(state.fill_count, 2);
+        assert_eq!(mm.funnel.orders_crossed, 10);
+    }
+
+    fn toxic_maker(state: &MarketState, window: usize) -> MarketMaker {
+        let config = MarketMakerConfig {
+            toxic_flow: Some(ToxicFlowConfig { window, threshold: 0.5, widening: 0.02, cooldown_steps: 3 }),
+            ..MarketMakerConfig::default()
+        };
+        maker(state, config)
+    }
+
+    fn quoted_spread(mm: &mut MarketMaker, state: &mut MarketState) -> f64 {
+        let quote = mm.quote(state).expect("a two-sided quote");
+        quote.ask - quote.bid
+    }
+
+    #[test]
+    fn toxic_fills_widen_and_benign_fills_do_not() {
+        let mut state = MarketState::new("toxic", 0.5);
+        let base = quoted_spread(&mut toxic_maker(&state, 4), &mut state);
+
+        let mut benign = toxic_maker(&state, 4);
+        for _ in 0..8 {
+            benign.record_markout(0.01);
+        }
+        assert_eq!(benign.toxic_cooldown, 0);
+        assert!((quoted_spread(&mut benign, &mut state) - base).abs() < 1e-12);
+
+        let mut toxic = toxic_maker(&state, 4);
+        for _ in 0..4 {
+            toxic.record_markout(-0.01);
+        }
+        assert_eq!(toxic.toxic_cooldown, 3);
+        for _ in 0..3 {
+            assert!((quoted_spread(&mut toxic, &mut state) - (base + 0.02)).abs() < 1e-12);
+        }
+        assert!((quoted_spread(&mut toxic, &mut state) - base).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zero_toxic_window_disables_widening() {
+        let mut state = MarketState::new("disabled", 0.5);
+        let mut mm = toxic_maker(&state, 0);
+        for _ in 0..10 {
+            mm.record_markout(-0.01);
+        }
+        assert_eq!(mm.toxic_cooldown, 0);
+        assert!(mm.adverse_fills.is_empty());
+        let base = quoted_spread(&mut maker(&state, MarketMakerConfig::default()), &mut state);
+        assert!((quoted_spread(&mut mm, &mut state) - base).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pre_tick_mid_batches_end_the_same_under_any_order() {
+        let config = MarketMakerConfig {
+            batch_fill_mode: BatchFillMode::PreTickMid,
+            ..MarketMakerConfig::default()
+        };
+        let mut orders = crossing_orders(8);
+        orders.extend(crossing_orders(3).into_iter().filter(|order| order.side == "buy"));
+
+        let end_of_step = |orders: &[Order]| {
+            let mut state = MarketState::new("batch", 0.5);
+            state.inventory_limit = 1000.0;
+            let mut mm = maker(&state, config.clone());
+            let quote = mm.quote(&mut state).expect("a two-sided quote");
+            mm.on_tick(&mut state, orders, &[quote]);
+            (state.pnl, state.inventory, state.mid)
+        };
+
+        let (pnl, inventory, mid) = end_of_step(&orders);
+        assert_eq!(inventory, -2.0); // six taker buys against four sells
+        let mut rng = ChaCha8Rng::seed_from_u64(240);
+        for _ in 0..50 {
+            orders.shuffle(&mut rng);
+            let permuted = end_of_step(&orders);
+            assert!((permuted.0 - pnl).abs() < 1e-12, "pnl {} vs {}", permuted.0, pnl);
+            assert_eq!(permuted.1, inventory);
+            assert!((permuted.2 - mid).abs() < 1e-12, "mid {} vs {}", permuted.2, mid);
+        }
+    }
+
+    #[test]
+    fn extreme_skew_near_the_top_never_crosses() {
+        for inventory in [1e6, -1e6] {
+            let mut state = MarketState::new("pinned", 0.99);
+            state.inventory = inventory;
+            state.inventory_limit = 100.0;
+            let config = MarketMakerConfig {
+                inventory_skew: 0.05,
+                ..MarketMakerConfig::default()
+            };
+            let min_spread = config.min_spread;
+            let mut mm = maker(&state, config);
+
+            let quote = mm.quote(&mut state).expect("widen keeps quoting");
+            assert!(quote.bid < quote.ask, "crossed quote {:?} at inventory {}", quote, inventory);
+            assert!(quote.ask - quote.bid >= min_spread - 1e-12);
+            assert!(quote.bid >= 0.0 && quote.ask <= 1.0);
+        }
+    }
+
+    fn taker_sells(n: usize) -> Vec<Order> {
+        (0..n)
+            .map(|_| Order { side: "sell".to_string(), size: 5.0, price: 0.0 })
+            .collect()
+    }
+
+    #[test]
+    fn relentless_one_sided_flow_forces_an_unwind_off_the_limit() {
+        let mut state = MarketState::new("stuck", 0.5);
+        state.inventory_limit = 20.0;
+        let config = MarketMakerConfig {
+            stuck_limit_steps: Some(3),
+            ..MarketMakerConfig::default()
+        };
+        let mut mm = maker(&state, config);
+
+        let mut reached_limit = false;
+        let mut unwound = false;
+        for _ in 0..30 {
+            let Some(quote) = mm.quote(&mut state) else { continue };
+            let fills = mm.on_tick(&mut state, &taker_sells(4), &[quote]);
+            reached_limit |= state.inventory >= 20.0 - 1.0;
+            if mm.stuck {
+                assert!(fills.iter().any(|fill| fill.side == "sell"), "stuck without an unwind fill");
+                assert!(state.inventory < 20.0 - 1.0);
+                unwound = true;
+                break;
+            }
+        }
+        assert!(reached_limit && unwound);
+    }
+
+    #[test]
+    fn last_trade_anchor_centers_quotes_on_the_last_fill() {
+        let mut state = MarketState::new("anchored", 0.5);
+        let config = MarketMakerConfig {
+            quote_anchor: QuoteAnchor::LastTrade,
+            inventory_skew: 0.0,
+            ..MarketMakerConfig::default()
+        };
+        let mut mm = maker(&state, config);
+        let quote = mm.quote(&mut state).expect("a two-sided quote");
+        assert!(((quote.bid + quote.ask) / 2.0 - 0.5).abs() < 1e-12, "no trades yet, so centered on mid");
+
+        let fills = mm.on_tick(&mut state, &taker_sells(1), &[quote]);
+        assert_eq!(fills.len(), 1);
+        let last_trade = state.last_trade_price.expect("the fill set a last trade");
+        assert!((last_trade - state.mid).abs() > 1e-3, "fill at the bid should sit off the new mid");
+
+        let quote = mm.quote(&mut state).expect("a two-sided quote");
+        let center = (quote.bid + quote.ask) / 2.0;
+        assert!((center - last_trade).abs() < 1e-12, "centered on {} not last trade {}", center, last_trade);
+    }
+
+    #[test]
+    fn the_same_inventory_skews_harder_near_resolution() {
+        let skew_at = |time: u64| {
+            let mut state = MarketState::new("expiring", 0.5);
+            state.inventory = 20.0;
+            state.resolution_step = Some(100);
+            let config = MarketMakerConfig {
+                resolution_urgency: 5.0,
+                ..MarketMakerConfig::default()
+            };
+            let mut mm = maker(&state, config);
+            mm.explain = true;
+            mm.time = time;
+            mm.quote(&mut state).expect("a two-sided quote");
+            mm.rationale.expect("explain builds a rationale").skew
+        };
+
+        let (early, late) = (skew_at(0), skew_at(99));
+        assert!(early > 0.0, "long inventory shades quotes down");
+        assert!(late > early, "skew {} near resolution vs {} far from it", late, early);
+    }
+
+    #[test]
+    fn no_fill_at_touch_ignores_orders_priced_exactly_at_the_quote() {
+        let fills_with = |policy: AtTouchPolicy| {
+            let mut state = MarketState::new("touch", 0.5);
+            let config = MarketMakerConfig {
+                at_touch_policy: policy,
+                ..MarketMakerConfig::default()
+            };
+            let mut mm = maker(&state, config);
+            let quote = mm.quote(&mut state).expect("a two-sided quote");
+            let at_touch = [
+                Order { side: "buy".to_string(), size: 1.0, price: quote.ask },
+                Order { side: "sell".to_string(), size: 1.0, price: quote.bid },
+            ];
+            mm.on_tick(&mut state, &at_touch, &[quote]).len()
+        };
+
+        assert_eq!(fills_with(AtTouchPolicy::NoFill), 0);
+        assert_eq!(fills_with(AtTouchPolicy::Fill), 2);
+    }
+
+    #[test]
+    fn a_winning_long_tightens_only_the_ask() {
+        let quote_with_entry = |entry: f64| {
+            let mut state = MarketState::new("winning", 0.5);
+            state.inventory = 10.0;
+            state.avg_entry_price = entry;
+            let config = MarketMakerConfig {
+                inventory_skew: 0.0,
+                profit_taking_coeff: 0.1,
+                ..MarketMakerConfig::default()
+            };
+            maker(&state, config).quote(&mut state).expect("a two-sided quote")
+        };
+
+        let flat = quote_with_entry(0.5);
+        let winning = quote_with_entry(0.4);
+        assert!(winning.ask < flat.ask - 1e-6, "ask {} vs {}", winning.ask, flat.ask);
+        assert!((winning.bid - flat.bid).abs() < 1e-12);
+        assert!(((flat.bid + flat.ask) / 2.0 - 0.5).abs() < 1e-12, "no gain, no lean");
+    }
+
+    #[test]
+    fn an_absurd_skew_trips_the_mid_shade_counter() {
+        let mut state = MarketState::new("skewed", 0.5);
+        state.inventory = 50.0;
+        let config = MarketMakerConfig {
+            inventory_skew: 1.0,
+            ..MarketMakerConfig::default()
+        };
+        let mut mm = maker(&state, config);
+        for _ in 0..3 {
+            mm.quote(&mut state);
+        }
+        assert_eq!(mm.clamps.mid_shade, 3);
+
+        let mut sane = maker(&state, MarketMakerConfig::default());
+        sane.quote(&mut state);
+        assert_eq!(sane.clamps.mid_shade, 0);
+    }
+
+    fn pid_maker(kp: f64, ki: f64) -> MarketMaker {
+        let config = MarketMakerConfig {
+            skew_strategy: SkewStrategy::PidSkew { kp, ki, kd: 0.0 },
+            ..MarketMakerConfig::default()
+        };
+        maker(&MarketState::new("pid", 0.5), config)
+    }
+
+    #[test]
+    fn pid_integral_removes_a_persistent_inventory_offset() {
+        // Toy plant: steady one-sided flow adds 2 a tick, and each unit of
+        // skew draws 100 units of offsetting flow
+        let settle = |mut mm: MarketMaker| {
+            let mut inventory: f64 = 0.0;
+            for _ in 0..500 {
+                inventory += 2.0 - 100.0 * mm.inventory_skew(inventory);
+            }
+            inventory
+        };
+
+        let proportional = settle(pid_maker(0.005, 0.0));
+        assert!((proportional - 4.0).abs() < 1e-3, "P alone settles at {}", proportional);
+        let integral = settle(pid_maker(0.005, 0.001));
+        assert!(integral.abs() < 1e-3, "PI settles at {}", integral);
+    }
+
+    #[test]
+    fn pid_integral_does_not_wind_up_while_saturated() {
+        let ki = 0.001;
+        let mut mm = pid_maker(0.0, ki);
+        for _ in 0..1000 {
+            mm.inventory_skew(1e4);
+        }
+        assert!((ki * mm.pid.integral - PID_INTEGRAL_SKEW_LIMIT).abs() < 1e-12);
+
+        // Unbounded, the integral would take 1000 opposing ticks to unwind
+        assert!(mm.inventory_skew(-1e4) < 0.0);
+    }
+
+    #[test]
+    fn a_zero_priced_fill_is_guarded_before_it_reaches_notional() {
+        let fill_at_zero = |guard: FillPriceGuard| {
+            let mut state = MarketState::new("floor", 0.01);
+            let config = MarketMakerConfig {
+                fill_price_guard: guard,
+                ..MarketMakerConfig::default()
+            };
+            let mut mm = maker(&state, config);
+            let quote = Quote { bid: 0.0, ask: 0.02, bid_size: 5.0, ask_size: 5.0 };
+            let order = Order { side: "sell".to_string(), size: 5.0, price: 0.0 };
+            let fills = mm.on_tick(&mut state, &[order], &[quote]);
+            assert_eq!(mm.clamps.fill_price, 1);
+            (fills, state)
+        };
+
+        let (fills, state) = fill_at_zero(FillPriceGuard::Clamp);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 0.001);
+        assert!((state.notional - 5.0 * 0.001).abs() < 1e-12);
+        assert!((state.avg_entry_price - 0.001).abs() < 1e-12);
+
+        let (fills, state) = fill_at_zero(FillPriceGuard::Reject);
+        assert!(fills.is_empty());
+        assert_eq!((state.notional, state.inventory, state.fill_count), (0.0, 0.0, 0));
+    }
+
+    #[test]
+    fn adaptive_window_shrinks_to_its_floor_under_heavy_flow() {
+        let mut state = MarketState::new("busy", 0.5);
+        state.inventory_limit = 1000.0;
+        let config = MarketMakerConfig {
+            imbalance_lookback: Some(20),
+            adaptive_window: Some(AdaptiveWindow { min_size: 5, max_size: 40, target_fill_rate: 1.0 }),
+            ..MarketMakerConfig::default()
+        };
+        let mut mm = maker(&state, config);
+        assert_eq!(mm.effective_window(), 40, "no fill history starts at the widest window");
+
+        let mut windows = Vec::new();
+        for _ in 0..40 {
+            let quote = mm.quote(&mut state).expect("a two-sided quote");
+            mm.on_tick(&mut state, &crossing_orders(20), &[quote]);
+            windows.push(mm.effective_window());
+        }
+        assert!(windows.windows(2).all(|pair| pair[1] <= pair[0]), "window grew: {:?}", windows);
+        assert_eq!(windows.last(), Some(&5));
+    }
+
+    #[test]
+    fn fills_never_carry_exposure_past_its_limit() {
+        for side in ["buy", "sell"] {
+            let mut state = MarketState::new("exposed", 0.5);
+            state.inventory_limit = 1000.0;
+            state.exposure_limit = 20.0;
+            let mut mm = maker(&state, MarketMakerConfig::default());
+            let orders: Vec<Order> = (0..6)
+                .map(|_| Order { side: side.to_string(), size: 8.0, price: if side == "buy" { 1.0 } else { 0.0 } })
+                .collect();
+
+            for _ in 0..20 {
+                let (mid, held) = (state.mid, state.inventory.abs());
+                let Some(quote) = mm.quote(&mut state) else { continue };
+                mm.on_tick(&mut state, &orders, &[quote]);
+                // Fills are priced against the quote's mid; the mark can
+                // drift past the limit later, but no fill grows the
+                // position beyond it
+                if state.inventory.abs() > held {
+                    assert!(
+                        state.inventory.abs() * mid <= state.exposure_limit + 1e-9,
+                        "taker {}s filled {} at mid {}",
+                        side, state.inventory, mid
+                    );
+                }
+            }
+            assert!(state.inventory.abs() * state.mid > 0.9 * state.exposure_limit, "{} flow never reached the limit", side);
+        }
+    }
+
+    fn fill_one_order(order_size: f64, quoted_size: f64) -> (Vec<FillResult>, MarketState) {
+        let mut state = MarketState::new("sized", 0.5);
+        let mut mm = maker(&state, MarketMakerConfig::default());
+        let quote = Quote { bid: 0.48, ask: 0.52, bid_size: quoted_size, ask_size: quoted_size };
+        let order = Order { side: "buy".to_string(), size: order_size, price: 1.0 };
+        let fills = mm.on_tick(&mut state, &[order], &[quote]);
+        (fills, state)
+    }
+
+    #[test]
+    fn an_order_larger_than_the_quote_fills_only_the_quoted_size() {
+        let (fills, state) = fill_one_order(12.0, 5.0);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size, 5.0);
+        assert_eq!(state.inventory, -5.0);
+    }
+
+    #[test]
+    fn an_order_matching_the_quote_fills_in_full() {
+        let (fills, state) = fill_one_order(5.0, 5.0);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size, 5.0);
+        assert_eq!(state.inventory, -5.0);
+    }
+
+    #[test]
+    fn an_order_smaller_than_the_quote_fills_its_own_size() {
+        let (fills, state) = fill_one_order(2.0, 5.0);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].size, 2.0);
+        assert_eq!(state.inventory, -2.0);
+    }
+
+    #[test]
+    fn ramp_up_quotes_are_wider_and_smaller_than_steady_state() {
+        let quote_after = |fills: u64| {
+            let mut state = MarketState::new("fresh", 0.5);
+            state.fill_count = fills;
+            let config = MarketMakerConfig {
+                rampup_fills: 10,
+                ..MarketMakerConfig::default()
+            };
+            maker(&state, config).quote(&mut state).expect("a two-sided quote")
+        };
+
+        let steady = quote_after(10);
+        let mut last = quote_after(0);
+        for fills in 1..=10 {
+            let next = quote_after(fills);
+            assert!(next.ask - next.bid <= last.ask - last.bid + 1e-12);
+            assert!(next.bid_size >= last.bid_size);
+            last = next;
+        }
+        let early = quote_after(2);
+        assert!(early.ask - early.bid > steady.ask - steady.bid + 1e-6);
+        assert!(early.bid_size < steady.bid_size && early.ask_size < steady.ask_size);
+        assert_eq!(quote_after(50), steady, "past the ramp nothing changes");
+    }
+
+    #[test]
+    fn avellaneda_stoikov_skews_against_the_position() {
+        let center_with = |inventory: f64| {
+            let mut state = MarketState::new("as", 0.5);
+            state.inventory = inventory;
+            state.resolution_step = Some(100);
+            let config = MarketMakerConfig {
+                quoting_model: QuotingModel::AvellanedaStoikov { gamma: 1.0, sigma: 0.01, k: 10.0 },
+                ..MarketMakerConfig::default()
+            };
+            let quote = maker(&state, config).quote(&mut state).expect("a two-sided quote");
+            (quote.bid + quote.ask) / 2.0 - 0.5
+        };
+
+        let (long, short) = (center_with(20.0), center_with(-20.0));
+        assert!(long < 0.0, "long inventory should shade quotes down, got {}", long);
+        assert!(short > 0.0, "short inventory should shade quotes up, got {}", short);
+        assert!((long + short).abs() < 1e-12, "skew is symmetric in inventory");
+        assert!(center_with(0.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn settlement_risk_charges_most_near_even_odds() {
+        let widening_at = |mid: f64| {
+            let mut state = MarketState::new("binary", mid);
+            state.resolution_step = Some(10);
+            let config = MarketMakerConfig {
+                settlement_risk_coeff: 0.2,
+                ..MarketMakerConfig::default()
+            };
+            let mut mm = maker(&state, config);
+            mm.explain = true;
+            mm.time = 8;
+            mm.quote(&mut state).expect("a two-sided quote");
+            mm.rationale.expect("explain builds a rationale").settlement_widening
+        };
+
+        let (even, longshot) = (widening_at(0.5), widening_at(0.05));
+        assert!(longshot > 0.0);
+        assert!(even > longshot, "{} at 0.5 vs {} at 0.05", even, longshot);
+        assert!((even / longshot - 0.25 / (0.05 * 0.95)).abs() < 1e-9, "scales with p(1 - p)");
+    }
+
+    #[test]
+    fn snapped_spread_is_the_intended_tick_count_at_any_mid() {
+        // (rounding, tick, spread, intended ticks): 0.01 / 0.003 rounds to 3;
+        // a 0.02 spread on a 0.05 grid can only land one tick wide, locked
+        // quotes included, which are widened to a single tick. min_spread
+        // sits below 3 ticks so its floor doesn't add a fourth
+        let cases = [
+            (TickRounding::PreserveSpread, 0.003, 0.01, 3.0),
+            (TickRounding::Independent, 0.05, 0.02, 1.0),
+        ];
+        for (rounding, tick, spread, intended) in cases {
+            for i in 0..400 {
+                let mut state = MarketState::new("grid", 0.05 + i as f64 * 0.00223);
+                state.spread = spread;
+                let config = MarketMakerConfig {
+                    min_spread: 0.005,
+                    tick_size: Some(tick),
+                    tick_rounding: rounding,
+                    ..MarketMakerConfig::default()
+                };
+                let mut mm = maker(&state, config);
+                let quote = mm.quote(&mut state).expect("a two-sided quote");
+                let ticks = (quote.ask - quote.bid) / tick;
+                assert!(
+                    (ticks - intended).abs() < 1e-6,
+                    "{:?} quoted {} ticks at mid {}: {:?}",
+                    rounding,
+                    ticks,
+                    state.mid,
+                    quote
+                );
+                assert!(on_grid(quote.bid, tick) && on_grid(quote.ask, tick));
+            }
+        }
+    }
+
+    #[test]
+    fn rationale_names_the_factors_that_moved_the_quote() {
+        let mut state = MarketState::new("why", 0.5);
+        let mut flat = maker(&state, MarketMakerConfig::default());
+        flat.explain = true;
+        let base = flat.quote(&mut state).expect("a two-sided quote");
+        let base_why = flat.rationale.clone().expect("explain is on");
+        assert!(base_why.summary.starts_with("base quote"), "{}", base_why.summary);
+        assert!((base.ask - base.bid - base_why.spread).abs() < 1e-12);
+
+        state.add_inventory_at_mid(45.0);
+        let mut mm = maker(&state, MarketMakerConfig::default());
+        mm.explain = true;
+        mm.imbalance_window.extend([1.0, 1.0, 1.0]);
+        let quote = mm.quote(&mut state).expect("a two-sided quote");
+        let why = mm.rationale.clone().expect("explain is on");
+
+        // Each reported factor is what actually moved the quote off base
+        assert!(why.imbalance_widening > 0.0 && why.inventory_widening > 0.0 && why.skew > 0.0);
+        let widening = why.imbalance_widening + why.inventory_widening;
+        assert!((why.spread - (base_why.spread + widening)).abs() < 1e-12);
+        assert!((quote.ask - quote.bid - why.spread).abs() < 1e-12);
+        assert!((state.mid - (quote.bid + quote.ask) / 2.0 - why.skew).abs() < 1e-12);
+        assert!((quote.bid_size - why.size).abs() < 1e-12 && why.size < base_why.size);
+
+        for expected in ["imbalance=3.00", "long inventory=45.0", "size reduced"] {
+            assert!(why.summary.contains(expected), "{:?} missing from {}", expected, why.summary);
+        }
+        for absent in ["toxic", "settlement", "ramp-up", "clamped", "profit"] {
+            assert!(!why.summary.contains(absent), "{:?} in {}", absent, why.summary);
+        }
+    }
+
+    #[test]
+    fn larger_orders_fill_at_worse_prices() {
+        let quote = Quote { bid: 0.45, ask: 0.55, bid_size: 40.0, ask_size: 40.0 };
+        let fill = |side: &str, size: f64| {
+            let mut state = MarketState::new("impact", 0.5);
+            let config = MarketMakerConfig {
+                price_impact: 0.001,
+                ..MarketMakerConfig::default()
+            };
+            let mut mm = maker(&state, config);
+            let price = if side == "buy" { 1.0 } else { 0.0 };
+            let order = Order { side: side.to_string(), size, price };
+            let fills = mm.on_tick(&mut state, &[order], &[quote]);
+            assert_eq!(fills.len(), 1);
+            assert_eq!(fills[0].size, size);
+            fills[0].price
+        };
+
+        // Taker buys pay up from the ask and taker sells give up from the bid
+        assert!((fill("buy", 1.0) - 0.551).abs() < 1e-12);
+        assert!((fill("buy", 30.0) - 0.58).abs() < 1e-12);
+        assert!((fill("sell", 1.0) - 0.449).abs() < 1e-12);
+        assert!((fill("sell", 30.0) - 0.42).abs() < 1e-12);
+    }
+
+    #[test]
+    fn imbalance_sums_exactly_the_lookback() {
+        let mut state = MarketState::new("lookback", 0.5);
+        let config = MarketMakerConfig {
+            window_size: 20,
+            imbalance_lookback: Some(3),
+            ..MarketMakerConfig::default()
+        };
+        let mut mm = maker(&state, config);
+        mm.explain = true;
+        // Oldest first: only the newest three count, not the full window
+        mm.imbalance_window.extend([50.0, 50.0, 50.0, 1.0, 2.0, 3.0]);
+        mm.quote(&mut state).expect("a two-sided quote");
+        let why = mm.rationale.expect("explain is on");
+        assert_eq!(why.imbalance, 6.0);
+        assert_eq!(why.imbalance_window, 3);
+    }
+
+    #[test]
+    fn two_takers_share_one_resting_level() {
+        let mut state = MarketState::new("queue", 0.5);
+        let mut mm = maker(&state, MarketMakerConfig::default());
+        let quote = Quote { bid: 0.45, ask: 0.55, bid_size: 10.0, ask_size: 10.0 };
+        let buy = Order { side: "buy".to_string(), size: 6.0, price: 1.0 };
+
+        let fills = mm.on_tick(&mut state, &[buy.clone(), buy.clone(), buy], &[quote]);
+        // The first taker takes 6, the second the 4 left and the third none
+        let sizes: Vec<f64> = fills.iter().map(|fill| fill.size).collect();
+        assert_eq!(sizes, vec![6.0, 4.0]);
+        assert!(fills.iter().all(|fill| fill.side == "sell" && fill.price == 0.55));
+        assert_eq!(mm.book.ask_depth(), 0.0);
+        assert_eq!(mm.book.bid_depth(), 10.0);
+        assert_eq!(state.inventory, -10.0);
+    }
 }
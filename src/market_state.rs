@@ -1,4 +1,7 @@
+use crate::market_maker::ClampCounters;
+use crate::metrics::PerformanceReport;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "wall-clock")]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -6,23 +9,91 @@ pub struct Fill {
     pub side: String,
     pub size: f64,
     pub price: f64,
-    pub timestamp: f64,
+    pub timestamp: f64, // engine step, or epoch seconds with the `wall-clock` feature
+    #[serde(default)]
+    pub step: u64,            // engine time; the logical clock
+    pub mid: f64,             // prevailing mid at fill time
+    pub realized_spread: f64, // edge vs the prevailing mid, positive when captured
+    pub markout: Option<f64>, // mid move in our favor after the markout horizon
+    #[serde(default)]
+    pub fee: f64, // fee charged on this fill, negative for a rebate
 }
 
 impl Fill {
-    pub fn new(side: &str, size: f64, price: f64) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64();
+    pub fn new(side: &str, size: f64, price: f64, mid: f64, step: u64) -> Self {
+        let timestamp = fill_timestamp(step);
+
+        // Buying below mid or selling above it captures spread
+        let realized_spread = match side {
+            "buy" => mid - price,
+            "sell" => price - mid,
+            _ => 0.0,
+        };
         
         Fill {
             side: side.to_string(),
             size,
             price,
             timestamp,
+            step,
+            mid,
+            realized_spread,
+            markout: None,
+            fee: 0.0,
         }
     }
+
+    /// Spread captured plus markout, times size, less the fee: the fill's
+    /// PnL marked at the post-horizon mid. None until the markout is taken.
+    pub fn net_edge(&self) -> Option<f64> {
+        self.markout
+            .map(|markout| self.size * (self.realized_spread + markout) - self.fee)
+    }
+}
+
+/// Stamp fills with the logical clock so identically seeded runs record
+/// identical fills
+#[cfg(not(feature = "wall-clock"))]
+fn fill_timestamp(step: u64) -> f64 {
+    step as f64
+}
+
+/// Live use: stamp fills with wall-clock seconds. A clock set before the
+/// epoch only loses the stamp; `step` still orders fills
+#[cfg(feature = "wall-clock")]
+fn fill_timestamp(_step: u64) -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+/// One market's step results as parallel columns, one entry per step traded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarketSeries {
+    pub mid: Vec<f64>,
+    pub inventory: Vec<f64>,
+    pub pnl: Vec<f64>, // cumulative
+    pub spread: Vec<f64>,
+}
+
+/// How the effective inventory limit is determined
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InventoryLimit {
+    /// Use `inventory_limit` as-is
+    Fixed,
+    /// Shares affordable with available capital at the current mid, times `fraction`
+    CapitalFraction { fraction: f64 },
+}
+
+/// Why a market stopped quoting before resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HaltReason {
+    /// Realized PnL hit `session_loss_cap` or `session_profit_cap`
+    SessionCap,
+    /// Available capital ran out; the position was flattened at the mark
+    CapitalExhausted,
+    /// Drawdown exceeded `max_drawdown_limit`; quoting only unwinds
+    Drawdown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,16 +103,116 @@ pub struct MarketState {
     pub spread: f64,        // absolute spread (probability points)
     pub inventory: f64,
     pub exposure: f64,
+    pub cash: f64,          // net cash from fills; pnl = cash + inventory * mid
     pub pnl: f64,
     pub fills: Vec<Fill>,
     pub fill_count: u64,
     pub notional: f64,
+    pub volume: f64,
+    pub last_trade_price: Option<f64>,
+    pub avg_entry_price: f64, // average cost of the open inventory
+    // pnl = realized_pnl + unrealized_pnl(); realized covers closed round
+    // trips at average cost, fees, rebates and settlement
+    #[serde(default)]
+    pub realized_pnl: f64,
     pub max_drawdown: f64,
     pub peak_pnl: f64,
+    // adverse-selection and quoting diagnostics
+    pub markout_sum: f64,     // size-weighted
+    pub markout_volume: f64,
+    #[serde(default)]
+    pub toxic_volume: f64, // marked-out volume whose markout went against us
+    // toxic_volume / markout_volume: the size-weighted share of fills the
+    // mid moved against within the markout horizon
+    #[serde(default)]
+    pub toxicity_score: f64,
+    pub quoted_spread_sum: f64,
+    pub quote_count: u64,
+    pub abs_inventory_sum: f64, // per-step |inventory| samples, for turnover
+    pub inventory_samples: u64,
+    #[serde(default)]
+    pub active_steps: u64, // steps this market traded, i.e. had at least one fill
     // risk parameters
     pub inventory_limit: f64,
+    pub inventory_limit_mode: InventoryLimit,
+    pub capital: f64, // starting capital; available capital is capital + pnl
+    // scheduled deposits (+) and withdrawals (-) as (step, amount)
+    #[serde(default)]
+    pub capital_events: Vec<(u64, f64)>,
     pub exposure_limit: f64,
+    // effective inventory limit is divided by 1 + vol_scale * volatility
+    #[serde(default)]
+    pub vol_scale: f64,
+    #[serde(default)]
+    pub volatility: f64, // EWMA std dev of per-step mid changes
     pub fee: f64,
+    #[serde(default)]
+    pub fees_paid: f64, // cumulative fees charged to cash, net of credited rebates
+    // gross bought / sold size; a net-flat book can still carry large legs
+    #[serde(default)]
+    pub gross_long_volume: f64,
+    #[serde(default)]
+    pub gross_short_volume: f64,
+    // maker rebates (negative fees) accrue as a claimable balance instead of
+    // hitting PnL immediately; claimed on settlement or via claim_rebates
+    pub accrue_rebates: bool,
+    pub accrued_rebates: f64,
+    // freeze the market (no quoting, still marked) once realized session PnL
+    // falls to -loss_cap or reaches profit_cap; open positions marked against
+    // us don't count until they are closed
+    #[serde(default)]
+    pub session_loss_cap: Option<f64>,
+    #[serde(default)]
+    pub session_profit_cap: Option<f64>,
+    #[serde(default)]
+    pub frozen: bool,
+    // once max_drawdown exceeds the limit the market is halted: it quotes
+    // only the side that reduces inventory until flat
+    #[serde(default)]
+    pub max_drawdown_limit: Option<f64>,
+    #[serde(default)]
+    pub halted: bool,
+    #[serde(default)]
+    pub halt_reason: Option<HaltReason>,
+    // PnL change over each step, for Sharpe/Sortino
+    #[serde(default)]
+    pub pnl_history: Vec<f64>,
+    // PnL, fills, notional and steps sampled at the end of the engine's
+    // warm-up; reported totals count from here
+    #[serde(default)]
+    pub warmup_pnl: f64,
+    #[serde(default)]
+    pub warmup_fill_count: u64,
+    #[serde(default)]
+    pub warmup_notional: f64,
+    #[serde(default)]
+    pub warmup_samples: u64,
+    // per-step values as reported in the step results, for plotting
+    #[serde(default)]
+    pub series: MarketSeries,
+    #[serde(default)]
+    pnl_sampled: f64, // PnL at the last sample
+    pub drift: f64, // per-step trend in mid; the reversion anchor moves with it
+    // fraction of the gap to the anchor the mid closes each step
+    #[serde(default = "default_reversion_rate")]
+    pub reversion_rate: f64,
+    #[serde(default = "default_reversion_anchor")]
+    pub reversion_anchor: f64, // fair value the mid reverts toward
+    // loading on the engine's shared factor shock; 0 = uncorrelated
+    #[serde(default)]
+    pub beta: f64,
+    pub resolution_step: Option<u64>, // engine step at which the market resolves
+    pub resolved: Option<bool>,       // outcome once settled; settled markets stop trading
+    // seed of this market's order-flow RNG; derived by the engine if unset
+    pub rng_seed: Option<u64>,
+}
+
+fn default_reversion_rate() -> f64 {
+    0.005
+}
+
+fn default_reversion_anchor() -> f64 {
+    0.5
 }
 
 impl MarketState {
@@ -52,31 +223,415 @@ impl MarketState {
             spread: 0.05,
             inventory: 0.0,
             exposure: 0.0,
+            cash: 0.0,
             pnl: 0.0,
             fills: Vec::new(),
             fill_count: 0,
             notional: 0.0,
+            volume: 0.0,
+            last_trade_price: None,
+            avg_entry_price: 0.0,
+            realized_pnl: 0.0,
             max_drawdown: 0.0,
             peak_pnl: 0.0,
+            markout_sum: 0.0,
+            markout_volume: 0.0,
+            toxic_volume: 0.0,
+            toxicity_score: 0.0,
+            quoted_spread_sum: 0.0,
+            quote_count: 0,
+            abs_inventory_sum: 0.0,
+            inventory_samples: 0,
+            active_steps: 0,
             inventory_limit: 100.0,
+            inventory_limit_mode: InventoryLimit::Fixed,
+            capital: 0.0,
+            capital_events: Vec::new(),
             exposure_limit: 10000.0,
+            vol_scale: 0.0,
+            volatility: 0.0,
             fee: 0.0,
+            fees_paid: 0.0,
+            gross_long_volume: 0.0,
+            gross_short_volume: 0.0,
+            accrue_rebates: false,
+            accrued_rebates: 0.0,
+            session_loss_cap: None,
+            max_drawdown_limit: None,
+            halted: false,
+            session_profit_cap: None,
+            frozen: false,
+            halt_reason: None,
+            pnl_history: Vec::new(),
+            warmup_pnl: 0.0,
+            warmup_fill_count: 0,
+            warmup_notional: 0.0,
+            warmup_samples: 0,
+            series: MarketSeries::default(),
+            pnl_sampled: 0.0,
+            drift: 0.0,
+            reversion_rate: default_reversion_rate(),
+            reversion_anchor: default_reversion_anchor(),
+            beta: 0.0,
+            resolution_step: None,
+            resolved: None,
+            rng_seed: None,
         }
     }
 
-    pub fn record_fill(&mut self, side: &str, size: f64, price: f64) {
-        let fill = Fill::new(side, size, price);
-        self.fills.push(fill);
+    pub fn record_fill(&mut self, side: &str, size: f64, price: f64, mid: f64, step: u64) {
+        let mut fill = Fill::new(side, size, price, mid, step);
         self.fill_count += 1;
         self.notional += size.abs() * price;
-        
-        match side {
-            "buy" => self.inventory += size,
-            "sell" => self.inventory -= size,
-            _ => {}
+        self.volume += size.abs();
+        self.last_trade_price = Some(price);
+
+        // Fees come out of cash (and so PnL) at fill time; rebates are
+        // negative fees, either credited the same way or accrued for later
+        let fee_amount = size.abs() * price * self.fee;
+        fill.fee = fee_amount;
+        self.fills.push(fill);
+        if fee_amount < 0.0 && self.accrue_rebates {
+            self.accrued_rebates -= fee_amount;
+        } else {
+            self.cash -= fee_amount;
+            self.fees_paid += fee_amount;
+            self.realized_pnl -= fee_amount;
         }
         
+        let delta = match side {
+            "buy" => size,
+            "sell" => -size,
+            _ => 0.0,
+        };
+        if delta > 0.0 {
+            self.gross_long_volume += delta;
+        } else {
+            self.gross_short_volume -= delta;
+        }
+        self.update_entry_price(delta, price);
+        self.inventory += delta;
+        self.cash -= delta * price;
+        self.mark_to_market();
+    }
+
+    /// Revalue the book and exposure at the current mid and update
+    /// peak/drawdown. A closed round trip leaves exactly the cash captured
+    /// between the fills.
+    pub fn mark_to_market(&mut self) {
         self.exposure = self.inventory.abs() * self.mid;
+        self.pnl = self.cash + self.inventory * self.mid;
+        self.peak_pnl = self.peak_pnl.max(self.pnl);
+        self.max_drawdown = self.max_drawdown.max(self.peak_pnl - self.pnl);
+    }
+
+    /// Apply the capital events scheduled for `step`
+    pub fn apply_capital_events(&mut self, step: u64) {
+        for &(event_step, amount) in &self.capital_events {
+            if event_step == step {
+                self.capital += amount;
+            }
+        }
+    }
+
+    pub fn available_capital(&self) -> f64 {
+        self.capital + self.pnl
+    }
+
+    /// Inventory limit in force right now; capital-derived limits tighten as
+    /// losses eat into available capital, and any limit tightens as the
+    /// market gets choppier
+    pub fn effective_inventory_limit(&self) -> f64 {
+        let base = match self.inventory_limit_mode {
+            InventoryLimit::Fixed => self.inventory_limit,
+            InventoryLimit::CapitalFraction { fraction } => {
+                let affordable = self.available_capital().max(0.0) / self.mid.max(0.01);
+                affordable * fraction
+            }
+        };
+        base / (1.0 + self.vol_scale * self.volatility)
+    }
+
+    /// Freeze the market once its realized PnL breaches a session cap;
+    /// returns true on the step it freezes
+    pub fn check_session_caps(&mut self) -> bool {
+        if self.frozen {
+            return false;
+        }
+        let lost = self.session_loss_cap.is_some_and(|cap| self.realized_pnl <= -cap);
+        let won = self.session_profit_cap.is_some_and(|cap| self.realized_pnl >= cap);
+        self.frozen = lost || won;
+        if self.frozen {
+            self.halt_reason = Some(HaltReason::SessionCap);
+        }
+        self.frozen
+    }
+
+    /// Halt the market once its drawdown exceeds `max_drawdown_limit`;
+    /// returns true on the step it halts
+    pub fn check_drawdown_limit(&mut self) -> bool {
+        if self.halted {
+            return false;
+        }
+        self.halted = self.max_drawdown_limit.is_some_and(|limit| self.max_drawdown > limit);
+        if self.halted {
+            self.halt_reason = Some(HaltReason::Drawdown);
+        }
+        self.halted
+    }
+
+    /// True once capital accounting is on and losses have used it all up
+    pub fn capital_exhausted(&self) -> bool {
+        self.capital > 0.0 && self.available_capital() <= 0.0
+    }
+
+    /// Close out the position at the current mid and stop quoting; returns
+    /// the closing fill, if there was a position to close
+    pub fn flatten_and_halt(&mut self, step: u64, reason: HaltReason) -> Option<Fill> {
+        self.frozen = true;
+        self.halt_reason = Some(reason);
+        if self.inventory == 0.0 {
+            return None;
+        }
+        let side = if self.inventory > 0.0 { "sell" } else { "buy" };
+        let mid = self.mid;
+        self.record_fill(side, self.inventory.abs(), mid, mid, step);
+        self.fills.last().cloned()
+    }
+
+    /// Largest absolute inventory allowed at the current mid: the inventory
+    /// limit, or less if the exposure limit binds first
+    pub fn max_position(&self) -> f64 {
+        let by_exposure = if self.mid > 0.0 {
+            self.exposure_limit / self.mid
+        } else {
+            f64::INFINITY
+        };
+        self.effective_inventory_limit().min(by_exposure)
+    }
+
+    /// Fold one step's mid change into the volatility EWMA
+    pub fn update_volatility(&mut self, previous_mid: f64) {
+        const ALPHA: f64 = 0.1;
+        let change = self.mid - previous_mid;
+        let variance = self.volatility * self.volatility;
+        self.volatility = (ALPHA * change * change + (1.0 - ALPHA) * variance).sqrt();
+    }
+
+    /// Take on `delta` inventory at the mid outside of any fill, e.g. for a
+    /// non-flat starting position; PnL is unchanged
+    pub fn add_inventory_at_mid(&mut self, delta: f64) {
+        let mid = self.mid;
+        self.update_entry_price(delta, mid);
+        self.inventory += delta;
+        self.cash -= delta * mid;
+        self.mark_to_market();
+    }
+
+    /// Average-cost basis: adding to the position blends the entry price,
+    /// reducing leaves it unchanged and realizes the closed part against it,
+    /// and flipping sides restarts it at `price`
+    fn update_entry_price(&mut self, delta: f64, price: f64) {
+        let inventory = self.inventory;
+        let new_inventory = inventory + delta;
+        
+        if inventory == 0.0 || inventory.signum() == delta.signum() {
+            let total = inventory.abs() + delta.abs();
+            if total > 0.0 {
+                self.avg_entry_price =
+                    (inventory.abs() * self.avg_entry_price + delta.abs() * price) / total;
+            }
+            return;
+        }
+        
+        let closed = delta.abs().min(inventory.abs());
+        self.realized_pnl += closed * (price - self.avg_entry_price) * inventory.signum();
+        if new_inventory == 0.0 {
+            self.avg_entry_price = 0.0;
+        } else if new_inventory.signum() != inventory.signum() {
+            self.avg_entry_price = price;
+        }
+    }
+
+    /// Average price the open inventory was acquired at; None when flat
+    pub fn open_position_cost_basis(&self) -> Option<f64> {
+        if self.inventory != 0.0 {
+            Some(self.avg_entry_price)
+        } else {
+            None
+        }
+    }
+
+    /// Mark-to-mid gain on the open inventory relative to its cost basis
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.inventory * (self.mid - self.avg_entry_price)
+    }
+
+    /// Fold accrued rebates into PnL, returning the amount claimed
+    pub fn claim_rebates(&mut self) -> f64 {
+        let claimed = self.accrued_rebates;
+        self.cash += claimed;
+        self.realized_pnl += claimed;
+        self.accrued_rebates = 0.0;
+        self.mark_to_market();
+        claimed
+    }
+
+    /// Settle the market at 1.0 (YES) or 0.0 (NO). Open inventory jumps from
+    /// the current mid to the payout and is closed out.
+    pub fn settle(&mut self, outcome: bool) {
+        let payout = if outcome { 1.0 } else { 0.0 };
+        self.cash += self.inventory * payout;
+        self.realized_pnl += self.inventory * (payout - self.avg_entry_price);
+        
+        self.inventory = 0.0;
+        self.avg_entry_price = 0.0;
+        self.exposure = 0.0;
+        self.mid = payout;
+        self.resolved = Some(outcome);
+        self.claim_rebates();
+    }
+
+    /// Record one end-of-step inventory observation
+    pub fn sample_inventory(&mut self) {
+        self.abs_inventory_sum += self.inventory.abs();
+        self.inventory_samples += 1;
+    }
+
+    /// Record this step's PnL change
+    pub fn sample_pnl(&mut self) {
+        self.pnl_history.push(self.pnl - self.pnl_sampled);
+        self.pnl_sampled = self.pnl;
+    }
+
+    /// Close the warm-up: restart drawdown, the return series and markout
+    /// stats from here, and baseline the reported PnL, fills and notional.
+    /// Trading state itself is untouched
+    pub fn end_warmup(&mut self) {
+        self.warmup_pnl = self.pnl;
+        self.warmup_fill_count = self.fill_count;
+        self.warmup_notional = self.notional;
+        self.warmup_samples = self.inventory_samples;
+        self.peak_pnl = self.pnl;
+        self.max_drawdown = 0.0;
+        self.pnl_history.clear();
+        self.markout_sum = 0.0;
+        self.markout_volume = 0.0;
+        self.toxic_volume = 0.0;
+        self.toxicity_score = 0.0;
+    }
+
+    /// PnL since the warm-up ended (all of it without a warm-up)
+    pub fn reported_pnl(&self) -> f64 {
+        self.pnl - self.warmup_pnl
+    }
+
+    /// Steps this market was live since the warm-up ended
+    pub fn reported_steps(&self) -> u64 {
+        self.inventory_samples - self.warmup_samples
+    }
+
+    /// Append this step's mid, inventory, PnL and spread to the series
+    pub fn record_series(&mut self) {
+        self.series.mid.push(self.mid);
+        self.series.inventory.push(self.inventory);
+        self.series.pnl.push(self.pnl);
+        self.series.spread.push(self.spread);
+    }
+
+    /// Fold a PnL change made after the step's sample (e.g. settlement)
+    /// into that step
+    pub fn amend_pnl_sample(&mut self) {
+        if let Some(last) = self.pnl_history.last_mut() {
+            *last += self.pnl - self.pnl_sampled;
+            self.pnl_sampled = self.pnl;
+        }
+    }
+
+    pub fn avg_abs_inventory(&self) -> f64 {
+        if self.inventory_samples > 0 {
+            self.abs_inventory_sum / self.inventory_samples as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Volume traded per unit of time-weighted average absolute inventory
+    pub fn turnover(&self) -> f64 {
+        let avg_inventory = self.avg_abs_inventory();
+        if avg_inventory > 0.0 {
+            self.volume / avg_inventory
+        } else if self.volume > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        }
+    }
+
+    /// Annualized PnL over max drawdown, counting the steps this market was
+    /// live (one inventory sample per step) after the warm-up
+    pub fn calmar_ratio(&self, steps_per_year: f64) -> f64 {
+        calmar_ratio(self.reported_pnl(), self.max_drawdown, self.reported_steps(), steps_per_year)
+    }
+
+    /// Percent of the effective inventory limit in use; None for a zero limit
+    pub fn inventory_utilization(&self) -> Option<f64> {
+        utilization(self.inventory.abs(), self.effective_inventory_limit())
+    }
+
+    /// Percent of the exposure limit in use; None for a zero limit
+    pub fn exposure_utilization(&self) -> Option<f64> {
+        utilization(self.exposure, self.exposure_limit)
+    }
+
+    /// Resolve the markout of a past fill against the current mid
+    pub fn record_markout(&mut self, fill_index: usize) -> f64 {
+        let fill = &mut self.fills[fill_index];
+        let direction = if fill.side == "buy" { 1.0 } else { -1.0 };
+        let markout = direction * (self.mid - fill.mid);
+        fill.markout = Some(markout);
+        self.markout_sum += markout * fill.size;
+        self.markout_volume += fill.size;
+        if markout < 0.0 {
+            self.toxic_volume += fill.size;
+        }
+        self.toxicity_score = self.toxic_volume / self.markout_volume;
+        markout
+    }
+
+    pub fn record_quote(&mut self, spread: f64) {
+        self.spread = spread;
+        self.quoted_spread_sum += spread;
+        self.quote_count += 1;
+    }
+
+    /// Size-weighted average markout per unit; negative means adverse selection
+    pub fn avg_markout(&self) -> f64 {
+        if self.markout_volume > 0.0 {
+            self.markout_sum / self.markout_volume
+        } else {
+            0.0
+        }
+    }
+
+    pub fn avg_quoted_spread(&self) -> f64 {
+        if self.quote_count > 0 {
+            self.quoted_spread_sum / self.quote_count as f64
+        } else {
+            self.spread
+        }
+    }
+
+    /// Minimum full spread that breaks even: each side's half-spread has to
+    /// cover the average adverse markout plus the fee on the fill price
+    pub fn breakeven_spread(&self) -> f64 {
+        let adverse = (-self.avg_markout()).max(0.0);
+        let avg_price = if self.volume > 0.0 {
+            self.notional / self.volume
+        } else {
+            self.mid
+        };
+        2.0 * (adverse + self.fee * avg_price)
     }
 
     pub fn snapshot(&self) -> MarketSnapshot {
@@ -89,11 +644,50 @@ impl MarketState {
             pnl: self.pnl,
             fill_count: self.fill_count,
             notional: self.notional,
+            fees_paid: self.fees_paid,
+            gross_long_volume: self.gross_long_volume,
+            gross_short_volume: self.gross_short_volume,
             max_drawdown: self.max_drawdown,
+            cost_basis: self.open_position_cost_basis(),
+            realized_pnl: self.realized_pnl,
+            unrealized_pnl: self.unrealized_pnl(),
+            avg_quoted_spread: self.avg_quoted_spread(),
+            breakeven_spread: self.breakeven_spread(),
+            toxicity_score: self.toxicity_score,
+            turnover: self.turnover(),
+            active_steps: self.active_steps,
+            calmar_ratio: 0.0,
+            performance: PerformanceReport::default(),
+            quote_clamps: ClampCounters::default(),
+            rng_seed: self.rng_seed,
         }
     }
 }
 
+/// Annualized return over max drawdown. With no drawdown the ratio is
+/// infinite for a gain and 0 when flat.
+pub fn calmar_ratio(pnl: f64, max_drawdown: f64, steps: u64, steps_per_year: f64) -> f64 {
+    if steps == 0 {
+        return 0.0;
+    }
+    let annualized = pnl * steps_per_year / steps as f64;
+    if max_drawdown > 0.0 {
+        annualized / max_drawdown
+    } else if annualized > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+fn utilization(value: f64, limit: f64) -> Option<f64> {
+    if limit > 0.0 {
+        Some(value / limit * 100.0)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketSnapshot {
     pub name: String,
@@ -104,5 +698,165 @@ pub struct MarketSnapshot {
     pub pnl: f64,
     pub fill_count: u64,
     pub notional: f64,
+    pub fees_paid: f64,
+    pub gross_long_volume: f64,
+    pub gross_short_volume: f64,
     pub max_drawdown: f64,
+    pub cost_basis: Option<f64>, // None when flat
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64, // pnl = realized_pnl + unrealized_pnl
+    pub avg_quoted_spread: f64,
+    pub breakeven_spread: f64,
+    #[serde(default)]
+    pub toxicity_score: f64, // share of marked-out volume the mid moved against
+    pub turnover: f64,
+    pub active_steps: u64,
+    // filled in by the engine, which knows the steps-per-year convention
+    pub calmar_ratio: f64,
+    pub performance: PerformanceReport, // filled in by the engine
+    pub quote_clamps: ClampCounters, // filled in from the maker by the engine
+    pub rng_seed: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capital_fraction_limit_tightens_as_losses_deplete_capital() {
+        let mut state = MarketState::new("funded", 0.5);
+        state.capital = 1000.0;
+        state.inventory_limit_mode = InventoryLimit::CapitalFraction { fraction: 0.5 };
+        let full = state.effective_inventory_limit();
+        assert!((full - 1000.0).abs() < 1e-9); // 0.5 * 1000 / 0.5
+
+        // Buy 100 at 0.5, then the mid falls: PnL and available capital drop
+        state.record_fill("buy", 100.0, 0.5, 0.5, 0);
+        state.mid = 0.3;
+        state.mark_to_market();
+        assert!(state.pnl < 0.0);
+        let depleted = state.effective_inventory_limit();
+        assert!((depleted - (1000.0 + state.pnl) * 0.5 / 0.3).abs() < 1e-9);
+
+        state.mid = 0.5;
+        state.cash -= 2000.0; // losses beyond the capital
+        state.mark_to_market();
+        assert!(state.effective_inventory_limit() < full);
+        assert_eq!(state.effective_inventory_limit(), 0.0);
+    }
+
+    #[test]
+    fn accrued_rebates_only_reach_pnl_once_claimed() {
+        let mut state = MarketState::new("rebates", 0.5);
+        state.fee = -0.01;
+        state.accrue_rebates = true;
+        state.record_fill("buy", 100.0, 0.49, 0.5, 0);
+        state.record_fill("sell", 100.0, 0.51, 0.5, 1);
+
+        let rebate = 0.01 * (100.0 * 0.49 + 100.0 * 0.51);
+        assert!((state.accrued_rebates - rebate).abs() < 1e-12);
+        assert!((state.pnl - 2.0).abs() < 1e-12); // the spread alone
+        assert_eq!(state.fees_paid, 0.0);
+
+        assert!((state.claim_rebates() - rebate).abs() < 1e-12);
+        assert!((state.pnl - (2.0 + rebate)).abs() < 1e-12);
+        assert_eq!(state.accrued_rebates, 0.0);
+    }
+
+    #[test]
+    fn round_trip_realizes_exactly_the_spread() {
+        let mut state = MarketState::new("round_trip", 0.5);
+        state.record_fill("buy", 40.0, 0.48, 0.5, 0);
+        state.record_fill("sell", 40.0, 0.52, 0.5, 1);
+
+        assert_eq!(state.inventory, 0.0);
+        assert!((state.realized_pnl - 0.04 * 40.0).abs() < 1e-12);
+        assert_eq!(state.unrealized_pnl(), 0.0);
+        assert!((state.pnl - state.realized_pnl).abs() < 1e-12);
+
+        let mut charged = MarketState::new("charged", 0.5);
+        charged.fee = 0.01;
+        charged.record_fill("buy", 40.0, 0.48, 0.5, 0);
+        charged.record_fill("sell", 40.0, 0.52, 0.5, 1);
+        let fees = 0.01 * 40.0 * (0.48 + 0.52);
+        assert!((charged.realized_pnl - (0.04 * 40.0 - fees)).abs() < 1e-12);
+        assert!((charged.pnl - charged.realized_pnl).abs() < 1e-12);
+    }
+
+    #[test]
+    fn volatile_mids_tighten_the_inventory_limit() {
+        let mut calm = MarketState::new("calm", 0.5);
+        calm.vol_scale = 50.0;
+        let mut choppy = calm.clone();
+
+        for step in 0..40 {
+            let previous = calm.mid;
+            calm.mid = 0.5 + if step % 2 == 0 { 0.001 } else { -0.001 };
+            calm.update_volatility(previous);
+
+            let previous = choppy.mid;
+            choppy.mid = 0.5 + if step % 2 == 0 { 0.05 } else { -0.05 };
+            choppy.update_volatility(previous);
+        }
+
+        assert!(choppy.volatility > calm.volatility);
+        assert!(choppy.effective_inventory_limit() < calm.effective_inventory_limit());
+        assert!(calm.effective_inventory_limit() < calm.inventory_limit);
+
+        // Without vol_scale the limit ignores volatility
+        choppy.vol_scale = 0.0;
+        assert_eq!(choppy.effective_inventory_limit(), choppy.inventory_limit);
+    }
+
+    #[test]
+    fn session_caps_ignore_an_unrealized_loss() {
+        let mut state = MarketState::new("marked_down", 0.5);
+        state.session_loss_cap = Some(5.0);
+        state.record_fill("buy", 100.0, 0.5, 0.5, 0);
+        state.mid = 0.3;
+        state.mark_to_market();
+        assert!(state.pnl <= -5.0, "marked 20 down: {}", state.pnl);
+        assert!(!state.check_session_caps());
+        assert!(!state.frozen);
+
+        // Closing the position realizes the loss
+        state.record_fill("sell", 100.0, 0.3, 0.3, 1);
+        state.mark_to_market();
+        assert!(state.check_session_caps());
+        assert_eq!(state.halt_reason, Some(HaltReason::SessionCap));
+    }
+
+    #[test]
+    fn offsetting_legs_show_gross_volume_a_flat_book_hides() {
+        let mut state = MarketState::new("churned", 0.5);
+        for step in 0..10 {
+            state.record_fill("buy", 500.0, 0.5, 0.5, step);
+            state.record_fill("sell", 499.9, 0.5, 0.5, step);
+        }
+        assert!(state.inventory.abs() < 1.5, "net {}", state.inventory);
+        assert_eq!(state.gross_long_volume, 5000.0);
+        assert!((state.gross_short_volume - 4999.0).abs() < 1e-9);
+        let snapshot = state.snapshot();
+        assert_eq!((snapshot.gross_long_volume, snapshot.gross_short_volume), (state.gross_long_volume, state.gross_short_volume));
+    }
+
+    #[test]
+    fn a_rebate_lifts_pnl_over_the_same_fills_without_fees() {
+        let run = |fee: f64| {
+            let mut state = MarketState::new("rebated", 0.5);
+            state.fee = fee;
+            state.record_fill("buy", 30.0, 0.48, 0.5, 0);
+            state.record_fill("sell", 20.0, 0.53, 0.5, 1);
+            state.record_fill("buy", 10.0, 0.47, 0.5, 2);
+            state.mark_to_market();
+            state
+        };
+        let (free, rebated) = (run(0.0), run(-0.001));
+
+        let notional = 30.0 * 0.48 + 20.0 * 0.53 + 10.0 * 0.47;
+        assert!(rebated.pnl > free.pnl);
+        assert!((rebated.pnl - free.pnl - 0.001 * notional).abs() < 1e-12);
+        assert!((rebated.fees_paid + 0.001 * notional).abs() < 1e-12, "fees_paid goes negative");
+        assert!(rebated.realized_pnl > free.realized_pnl);
+    }
 }
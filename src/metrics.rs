@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// Risk-adjusted performance of a per-step PnL series. Ratios are
+/// annualized with `steps_per_year`, like the Calmar ratio.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceReport {
+    pub steps: usize,
+    pub mean_step_pnl: f64,
+    pub annualized_volatility: f64,
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64,
+}
+
+impl PerformanceReport {
+    /// `risk_free` is the per-step PnL a riskless book would earn
+    pub fn new(pnl_series: &[f64], risk_free: f64, steps_per_year: f64) -> Self {
+        let annualize = steps_per_year.max(0.0).sqrt();
+        PerformanceReport {
+            steps: pnl_series.len(),
+            mean_step_pnl: mean(pnl_series),
+            annualized_volatility: std_dev(pnl_series) * annualize,
+            sharpe_ratio: sharpe(pnl_series, risk_free) * annualize,
+            sortino_ratio: sortino(pnl_series, risk_free) * annualize,
+        }
+    }
+}
+
+/// Per-step Sharpe ratio: mean excess PnL over its standard deviation, 0
+/// when the series is too short or flat
+pub fn sharpe(pnl_series: &[f64], risk_free: f64) -> f64 {
+    let deviation = std_dev(pnl_series);
+    if deviation > 0.0 {
+        (mean(pnl_series) - risk_free) / deviation
+    } else {
+        0.0
+    }
+}
+
+/// Per-step Sortino ratio: like Sharpe, but only steps below `risk_free`
+/// count as risk. As with Calmar, no downside makes it infinite for a gain
+/// and 0 otherwise
+pub fn sortino(pnl_series: &[f64], risk_free: f64) -> f64 {
+    if pnl_series.is_empty() {
+        return 0.0;
+    }
+    let downside = pnl_series
+        .iter()
+        .map(|pnl| (pnl - risk_free).min(0.0).powi(2))
+        .sum::<f64>()
+        / pnl_series.len() as f64;
+    let excess = mean(pnl_series) - risk_free;
+    if downside > 0.0 {
+        excess / downside.sqrt()
+    } else if excess > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+pub fn mean(series: &[f64]) -> f64 {
+    if series.is_empty() {
+        0.0
+    } else {
+        series.iter().sum::<f64>() / series.len() as f64
+    }
+}
+
+/// Sample standard deviation
+pub fn std_dev(series: &[f64]) -> f64 {
+    if series.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(series);
+    let variance = series.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (series.len() - 1) as f64;
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sortino_without_downside_matches_calmar() {
+        assert_eq!(sortino(&[0.5, 1.0, 0.25], 0.0), f64::INFINITY);
+        assert_eq!(sortino(&[0.0, 0.0], 0.0), 0.0);
+        assert_eq!(sortino(&[], 0.0), 0.0);
+        assert!(sortino(&[1.0, -1.0, 2.0], 0.0).is_finite());
+    }
+}
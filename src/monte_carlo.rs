@@ -0,0 +1,229 @@
+use crate::execution_engine::{derive_market_seed, ExecutionEngine};
+use crate::market_state::MarketState;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Percentiles of portfolio equity across runs at one step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquityBand {
+    pub step: usize,
+    pub p5: f64,
+    pub p50: f64,
+    pub p95: f64,
+}
+
+/// One Monte Carlo run: its seed, starting position and equity curve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McRun {
+    pub seed: u64,
+    pub start_inventory: f64, // mean |inventory| / max position at the start; 0 when flat
+    pub curve: Vec<f64>,
+}
+
+impl McRun {
+    pub fn final_pnl(&self) -> f64 {
+        self.curve.last().copied().unwrap_or(0.0)
+    }
+}
+
+/// Mean final PnL of the runs whose starting inventory falls in a band
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartInventoryBucket {
+    pub min_start: f64,
+    pub max_start: f64,
+    pub runs: usize,
+    pub mean_final_pnl: f64,
+}
+
+/// Run one simulation per seed on a fresh engine from `build_engine`, e.g.
+/// `|seed| configured.fresh_run(markets.clone(), seed)`, and return each
+/// run's portfolio equity curve (total PnL after every step)
+pub fn run_equity_curves<F>(build_engine: F, seeds: &[u64], steps: usize) -> Vec<Vec<f64>>
+where
+    F: Fn(u64) -> ExecutionEngine,
+{
+    run_monte_carlo(build_engine, seeds, steps, None)
+        .into_iter()
+        .map(|run| run.curve)
+        .collect()
+}
+
+/// Like `run_equity_curves`, optionally starting each market of each run
+/// at a random inventory within `random_initial_inventory` times its max
+/// position, drawn from the run's seed so every start is reproducible
+pub fn run_monte_carlo<F>(
+    build_engine: F,
+    seeds: &[u64],
+    steps: usize,
+    random_initial_inventory: Option<f64>,
+) -> Vec<McRun>
+where
+    F: Fn(u64) -> ExecutionEngine,
+{
+    seeds
+        .iter()
+        .map(|&seed| {
+            let mut engine = build_engine(seed);
+            let start_inventory = match random_initial_inventory {
+                Some(fraction) => randomize_inventory(&mut engine.markets, fraction, seed),
+                None => 0.0,
+            };
+            let mut curve = Vec::with_capacity(steps);
+            engine.run_with(steps, |engine, _| {
+                curve.push(engine.total_pnl());
+            });
+            McRun {
+                seed,
+                start_inventory,
+                curve,
+            }
+        })
+        .collect()
+}
+
+/// Open a random position in every market, bought at the mid so PnL starts
+/// at zero. Returns the mean |inventory| as a fraction of max position.
+pub fn randomize_inventory(markets: &mut HashMap<String, MarketState>, fraction: f64, seed: u64) -> f64 {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let mut rng = ChaCha8Rng::seed_from_u64(derive_market_seed(seed, "__initial_inventory__"));
+    let mut states: Vec<&mut MarketState> = markets.values_mut().collect();
+    states.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut total = 0.0;
+    for state in states.iter_mut() {
+        let limit = state.max_position();
+        let share: f64 = rng.gen_range(-1.0..=1.0) * fraction;
+        let inventory = (share * limit).round();
+        state.add_inventory_at_mid(inventory);
+        total += if limit > 0.0 { inventory.abs() / limit } else { 0.0 };
+    }
+    if states.is_empty() {
+        0.0
+    } else {
+        total / states.len() as f64
+    }
+}
+
+/// Mean final PnL per equal-width band of starting inventory, from flat up
+/// to the largest start seen
+pub fn pnl_by_start_inventory(runs: &[McRun], buckets: usize) -> Vec<StartInventoryBucket> {
+    let buckets = buckets.max(1);
+    let widest = runs.iter().map(|run| run.start_inventory).fold(0.0, f64::max);
+    let width = if widest > 0.0 { widest / buckets as f64 } else { 1.0 };
+
+    (0..buckets)
+        .map(|bucket| {
+            let (min_start, max_start) = (bucket as f64 * width, (bucket + 1) as f64 * width);
+            let last = bucket == buckets - 1;
+            let pnls: Vec<f64> = runs
+                .iter()
+                .filter(|run| run.start_inventory >= min_start && (run.start_inventory < max_start || last))
+                .map(McRun::final_pnl)
+                .collect();
+            StartInventoryBucket {
+                min_start,
+                max_start,
+                runs: pnls.len(),
+                mean_final_pnl: if pnls.is_empty() { 0.0 } else { pnls.iter().sum::<f64>() / pnls.len() as f64 },
+            }
+        })
+        .collect()
+}
+
+/// Per-step p5/p50/p95 across equity curves. Runs can stop early, so curves
+/// are aligned on step index and truncated to the shortest.
+pub fn equity_bands(curves: &[Vec<f64>]) -> Vec<EquityBand> {
+    let len = curves.iter().map(|c| c.len()).min().unwrap_or(0);
+
+    (0..len)
+        .map(|step| {
+            let mut values: Vec<f64> = curves.iter().map(|c| c[step]).collect();
+            values.sort_by(|a, b| a.total_cmp(b));
+            EquityBand {
+                step,
+                p5: percentile(&values, 0.05),
+                p50: percentile(&values, 0.50),
+                p95: percentile(&values, 0.95),
+            }
+        })
+        .collect()
+}
+
+/// Linear-interpolated percentile of an ascending slice
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = q * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f64;
+    sorted[lower] * (1.0 - weight) + sorted[upper] * weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_flow::OrderFlowModel;
+
+    fn markets() -> HashMap<String, MarketState> {
+        ["alpha", "beta"]
+            .iter()
+            .map(|name| (name.to_string(), MarketState::new(name, 0.5)))
+            .collect()
+    }
+
+    #[test]
+    fn runs_simulate_the_configured_engine() {
+        let mut configured = ExecutionEngine::new(markets(), 225).unwrap();
+        configured.set_orders_per_tick(0, 0).unwrap();
+        configured.warmup_steps = 10;
+        configured.flow_model = Box::new(OrderFlowModel::Adversarial);
+
+        let run = configured.fresh_run(markets(), 7);
+        assert_eq!(run.orders_per_tick(), (0, 0));
+        assert_eq!(run.flow_model.builtin(), Some(OrderFlowModel::Adversarial));
+        assert_eq!(run.warmup_steps, 10);
+        assert_eq!(run.rng_seed, 7);
+
+        // No orders per tick, so no fills: every curve stays flat at zero
+        let curves = run_equity_curves(|seed| configured.fresh_run(markets(), seed), &[1, 2, 3], 20);
+        assert_eq!(curves.len(), 3);
+        assert!(curves.iter().all(|curve| curve.len() == 20 && curve.iter().all(|pnl| *pnl == 0.0)));
+
+        // The default flow does fill, so the settings really were carried
+        let default_curves = run_equity_curves(|seed| ExecutionEngine::new(markets(), seed).unwrap(), &[1], 20);
+        assert!(default_curves[0].iter().any(|pnl| *pnl != 0.0));
+    }
+
+    #[test]
+    fn equity_bands_truncate_to_the_shortest_run() {
+        let bands = equity_bands(&[vec![1.0, 2.0, 3.0], vec![3.0, 4.0], vec![2.0, 6.0, 9.0]]);
+        assert_eq!(bands.len(), 2);
+        assert_eq!((bands[0].p50, bands[1].p50), (2.0, 4.0));
+        assert!((bands[1].p5 - 2.2).abs() < 1e-12);
+    }
+
+    #[test]
+    #[cfg(not(feature = "wall-clock"))]
+    fn flat_and_random_starts_differ_and_each_reproduces() {
+        let build = |seed| ExecutionEngine::new(markets(), seed).unwrap();
+        let curves = |runs: Vec<McRun>| -> Vec<(f64, Vec<f64>)> {
+            runs.into_iter().map(|run| (run.start_inventory, run.curve)).collect()
+        };
+        let seeds = [1, 2, 3];
+
+        let flat = curves(run_monte_carlo(build, &seeds, 50, None));
+        let random = curves(run_monte_carlo(build, &seeds, 50, Some(0.5)));
+        assert_eq!(flat, curves(run_monte_carlo(build, &seeds, 50, None)));
+        assert_eq!(random, curves(run_monte_carlo(build, &seeds, 50, Some(0.5))));
+
+        assert!(flat.iter().all(|(start, _)| *start == 0.0));
+        assert!(random.iter().all(|(start, _)| *start > 0.0));
+        for ((_, flat_curve), (_, random_curve)) in flat.iter().zip(&random) {
+            assert_ne!(flat_curve, random_curve);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::diffusion::standard_normal;
+use crate::market_maker::Order;
+use crate::market_state::MarketState;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Taker flow generator the engine draws each market's orders from. The
+/// built-in `OrderFlowModel`s implement it; embedders can plug in their own
+pub trait OrderFlow: Send {
+    /// Fill `orders` with this tick's taker orders, clearing it first.
+    /// `orders_per_tick` is the engine's (min, max) for uniform counts
+    fn generate(
+        &mut self,
+        state: &MarketState,
+        rng: &mut ChaCha8Rng,
+        orders_per_tick: (u32, u32),
+        orders: &mut Vec<Order>,
+    );
+
+    /// An independent copy, e.g. for a Monte Carlo run
+    fn clone_box(&self) -> Box<dyn OrderFlow>;
+
+    /// The built-in model this is, if any. Only built-in flow is saved in
+    /// checkpoints; an engine running anything else fails to serialize
+    fn builtin(&self) -> Option<OrderFlowModel> {
+        None
+    }
+}
+
+/// Built-in taker flow. Plain data, so engines running it still
+/// checkpoint through serde
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OrderFlowModel {
+    /// A uniform count of orders per tick (1-3 by default), buying more
+    /// often the higher the mid
+    MidBiased,
+    /// Poisson(lambda) mid-biased orders per tick
+    Poisson { lambda: f64 },
+    /// Informed flow that trades into our position, so we get longer when
+    /// long and shorter when short; mid-biased while we are flat
+    Adversarial,
+}
+
+impl OrderFlowModel {
+    /// Fill `orders` with this tick's taker orders; it is cleared first so
+    /// one buffer serves every tick. The uniform models draw between `min`
+    /// and `max` orders, inclusive
+    pub fn generate<R: Rng>(
+        &self,
+        state: &MarketState,
+        rng: &mut R,
+        (min, max): (u32, u32),
+        orders: &mut Vec<Order>,
+    ) {
+        orders.clear();
+
+        let n = match *self {
+            OrderFlowModel::MidBiased | OrderFlowModel::Adversarial => rng.gen_range(min..=max),
+            OrderFlowModel::Poisson { lambda } => poisson(rng, lambda),
+        };
+
+        for _ in 0..n {
+            // Bias toward mid: higher mid -> more buys, lower mid -> more sells
+            let noise: f64 = rng.gen_range(-0.15..0.15);
+            let prob = state.mid + noise;
+
+            let side = match self {
+                OrderFlowModel::Adversarial if state.inventory > 0.0 => "sell",
+                OrderFlowModel::Adversarial if state.inventory < 0.0 => "buy",
+                _ if prob > 0.5 => "buy",
+                _ => "sell",
+            };
+
+            // Size follows a normal-ish distribution clamped to [1, 30]
+            let size: f64 = (rng.gen::<f64>() * 4.0 + 4.0).clamp(1.0, 30.0);
+
+            // Price: buyers willing to pay 1.0, sellers accept 0.0
+            let price = if side == "buy" { 1.0 } else { 0.0 };
+
+            orders.push(Order {
+                side: side.to_string(),
+                size,
+                price,
+            });
+        }
+    }
+}
+
+impl OrderFlow for OrderFlowModel {
+    fn generate(
+        &mut self,
+        state: &MarketState,
+        rng: &mut ChaCha8Rng,
+        orders_per_tick: (u32, u32),
+        orders: &mut Vec<Order>,
+    ) {
+        OrderFlowModel::generate(self, state, rng, orders_per_tick, orders);
+    }
+
+    fn clone_box(&self) -> Box<dyn OrderFlow> {
+        Box::new(*self)
+    }
+
+    fn builtin(&self) -> Option<OrderFlowModel> {
+        Some(*self)
+    }
+}
+
+/// Serde for a `Box<dyn OrderFlow>` field, through its built-in model
+pub(crate) mod boxed {
+    use super::*;
+
+    // serde's `with` passes the field itself, the Box, by reference
+    #[allow(clippy::borrowed_box)]
+    pub fn serialize<S: Serializer>(flow: &Box<dyn OrderFlow>, serializer: S) -> Result<S::Ok, S::Error> {
+        builtin(flow.as_ref())?.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<dyn OrderFlow>, D::Error> {
+        Ok(Box::new(OrderFlowModel::deserialize(deserializer)?))
+    }
+
+    pub(super) fn builtin<E: serde::ser::Error>(flow: &dyn OrderFlow) -> Result<OrderFlowModel, E> {
+        flow.builtin()
+            .ok_or_else(|| E::custom("only built-in order flow models can be serialized"))
+    }
+}
+
+/// Serde for a per-market `HashMap<String, Box<dyn OrderFlow>>` field
+pub(crate) mod boxed_map {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        flows: &HashMap<String, Box<dyn OrderFlow>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let models = flows
+            .iter()
+            .map(|(name, flow)| Ok((name, boxed::builtin(flow.as_ref())?)))
+            .collect::<Result<HashMap<_, _>, S::Error>>()?;
+        models.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, Box<dyn OrderFlow>>, D::Error> {
+        let models = HashMap::<String, OrderFlowModel>::deserialize(deserializer)?;
+        Ok(models
+            .into_iter()
+            .map(|(name, model)| (name, Box::new(model) as Box<dyn OrderFlow>))
+            .collect())
+    }
+}
+
+/// Poisson draw: Knuth's method for small lambda, a rounded normal
+/// approximation above 30 where exp(-lambda) gets too small to multiply down to
+fn poisson<R: Rng>(rng: &mut R, lambda: f64) -> u32 {
+    if lambda <= 0.0 {
+        return 0;
+    }
+    if lambda > 30.0 {
+        return (lambda + lambda.sqrt() * standard_normal(rng)).round().max(0.0) as u32;
+    }
+
+    let limit = (-lambda).exp();
+    let mut product = rng.gen::<f64>();
+    let mut count = 0;
+    while product > limit {
+        product *= rng.gen::<f64>();
+        count += 1;
+    }
+    count
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Source of reference mids: a JSON object of market name -> mid, e.g.
+/// `{"team_x_wins": 0.62}`. `FileFeed` and `HttpFeed` are built in;
+/// embedders can poll their own
+pub trait PriceFeed: Send {
+    fn fetch(&mut self) -> Result<HashMap<String, f64>, Box<dyn Error>>;
+
+    /// The built-in source this is, if any. Only built-in feeds are saved in
+    /// checkpoints; an engine polling anything else fails to serialize
+    fn builtin(&self) -> Option<PriceFeedSource> {
+        None
+    }
+}
+
+/// A file re-read on every poll
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileFeed {
+    pub path: String,
+}
+
+impl PriceFeed for FileFeed {
+    fn fetch(&mut self) -> Result<HashMap<String, f64>, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(&self.path)?)?)
+    }
+
+    fn builtin(&self) -> Option<PriceFeedSource> {
+        Some(PriceFeedSource::File(self.clone()))
+    }
+}
+
+/// A plain `http://` endpoint fetched with a GET on every poll
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpFeed {
+    pub url: String,
+}
+
+impl PriceFeed for HttpFeed {
+    fn fetch(&mut self) -> Result<HashMap<String, f64>, Box<dyn Error>> {
+        Ok(serde_json::from_str(&http_get(&self.url)?)?)
+    }
+
+    fn builtin(&self) -> Option<PriceFeedSource> {
+        Some(PriceFeedSource::Http(self.clone()))
+    }
+}
+
+/// One of the built-in feeds, as parsed from the command line and saved in
+/// checkpoints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PriceFeedSource {
+    File(FileFeed),
+    Http(HttpFeed),
+}
+
+impl PriceFeedSource {
+    /// `http://...` is an endpoint, anything else a file path. `https://`
+    /// is rejected up front, since polling has no TLS to fetch it with
+    pub fn parse(value: &str) -> Result<Self, Box<dyn Error>> {
+        if value.starts_with("https://") {
+            Err(format!("https not supported for price feeds, use http:// or a file: {}", value).into())
+        } else if value.starts_with("http://") {
+            Ok(PriceFeedSource::Http(HttpFeed { url: value.to_string() }))
+        } else {
+            Ok(PriceFeedSource::File(FileFeed { path: value.to_string() }))
+        }
+    }
+
+    pub fn into_feed(self) -> Box<dyn PriceFeed> {
+        match self {
+            PriceFeedSource::File(feed) => Box::new(feed),
+            PriceFeedSource::Http(feed) => Box::new(feed),
+        }
+    }
+}
+
+/// External reference price the mids revert toward in place of the synthetic
+/// drift, polled every `poll_interval` steps
+#[derive(Serialize, Deserialize)]
+pub struct PolledFeed {
+    #[serde(serialize_with = "serialize_feed", deserialize_with = "deserialize_feed")]
+    pub source: Box<dyn PriceFeed>,
+    pub poll_interval: u64,
+    pub reversion: f64, // fraction of the gap to the reference closed per step
+    // last good value per market, held through failed polls
+    #[serde(default)]
+    pub last_known: HashMap<String, f64>,
+}
+
+impl PolledFeed {
+    pub fn new(source: Box<dyn PriceFeed>, poll_interval: u64) -> Self {
+        PolledFeed {
+            source,
+            poll_interval: poll_interval.max(1),
+            reversion: 0.2,
+            last_known: HashMap::new(),
+        }
+    }
+
+    /// Re-read the feed if a poll is due at `step`; on an error, or for
+    /// values outside (0, 1), the last known values are kept
+    pub fn poll(&mut self, step: u64) {
+        if !step.is_multiple_of(self.poll_interval) {
+            return;
+        }
+        match self.source.fetch() {
+            Ok(mids) => {
+                for (market, mid) in mids {
+                    if mid.is_finite() && mid > 0.0 && mid < 1.0 {
+                        self.last_known.insert(market, mid);
+                    }
+                }
+            }
+            Err(e) => eprintln!("⚠️  price feed poll failed at step {}: {} (holding last values)", step, e),
+        }
+    }
+
+    pub fn reference(&self, market: &str) -> Option<f64> {
+        self.last_known.get(market).copied()
+    }
+}
+
+// serde's `serialize_with` passes the field itself, the Box, by reference
+#[allow(clippy::borrowed_box)]
+fn serialize_feed<S: Serializer>(feed: &Box<dyn PriceFeed>, serializer: S) -> Result<S::Ok, S::Error> {
+    feed.builtin()
+        .ok_or_else(|| serde::ser::Error::custom("only built-in price feeds can be serialized"))?
+        .serialize(serializer)
+}
+
+fn deserialize_feed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<dyn PriceFeed>, D::Error> {
+    Ok(PriceFeedSource::deserialize(deserializer)?.into_feed())
+}
+
+/// Minimal HTTP/1.0 GET; there is no TLS, so only `http://` is supported
+fn http_get(url: &str) -> Result<String, Box<dyn Error>> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or("only plain http:// price feeds are supported")?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let address = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    let socket = address.to_socket_addrs()?.next().ok_or("price feed host did not resolve")?;
+
+    let timeout = Duration::from_secs(2);
+    let mut stream = TcpStream::connect_timeout(&socket, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    write!(stream, "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response.split_once("\r\n\r\n").ok_or("malformed HTTP response")?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("price feed returned '{}'", status).into());
+    }
+    Ok(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_https_feeds() {
+        assert_eq!(
+            PriceFeedSource::parse("http://localhost:8080/mids").unwrap(),
+            PriceFeedSource::Http(HttpFeed { url: "http://localhost:8080/mids".to_string() })
+        );
+        assert_eq!(
+            PriceFeedSource::parse("mids.json").unwrap(),
+            PriceFeedSource::File(FileFeed { path: "mids.json".to_string() })
+        );
+        let error = PriceFeedSource::parse("https://example.com/mids").unwrap_err();
+        assert!(error.to_string().contains("https not supported"), "{}", error);
+    }
+
+    #[test]
+    fn file_feed_rereads_its_file_and_holds_through_bad_polls() {
+        let path = std::env::temp_dir().join(format!("price_feed_test_{}.json", std::process::id()));
+        let path_str = path.to_str().unwrap().to_string();
+        fs::write(&path, r#"{"alpha": 0.62, "beta": 0.3}"#).unwrap();
+        let mut feed = FileFeed { path: path_str.clone() };
+        assert_eq!(feed.fetch().unwrap(), HashMap::from([("alpha".to_string(), 0.62), ("beta".to_string(), 0.3)]));
+
+        let mut polled = PolledFeed::new(Box::new(feed), 2);
+        polled.poll(0);
+        assert_eq!(polled.reference("alpha"), Some(0.62));
+
+        fs::write(&path, r#"{"alpha": 0.7, "beta": 1.5}"#).unwrap();
+        polled.poll(1); // not due
+        assert_eq!(polled.reference("alpha"), Some(0.62));
+        polled.poll(2);
+        assert_eq!(polled.reference("alpha"), Some(0.7));
+        assert_eq!(polled.reference("beta"), Some(0.3), "out-of-range values are ignored");
+
+        fs::write(&path, "not json").unwrap();
+        polled.poll(4);
+        assert_eq!(polled.reference("alpha"), Some(0.7));
+
+        let restored: PolledFeed = serde_json::from_str(&serde_json::to_string(&polled).unwrap()).unwrap();
+        assert_eq!(restored.source.builtin(), Some(PriceFeedSource::File(FileFeed { path: path_str })));
+        assert_eq!(restored.reference("alpha"), Some(0.7));
+        fs::remove_file(&path).unwrap();
+    }
+}
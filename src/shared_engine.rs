@@ -0,0 +1,109 @@
+use crate::execution_engine::{ExecutionEngine, StepResult};
+use crate::market_state::MarketSnapshot;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Thread-safe handle for stepping the engine on one thread while others
+/// read snapshots. Clones share the same engine.
+///
+/// Locking discipline: the engine sits behind a mutex only the stepping side
+/// takes. After every step (or `with_engine` call) the stepper publishes a
+/// fresh snapshot map, swapping an `Arc` under a short write lock while
+/// still holding the engine mutex. Readers take only the read lock to clone
+/// that `Arc`, so they never wait on a step in progress and never see a half
+/// stepped market. Locks are always taken engine first, then snapshots,
+/// which rules out lock-order deadlocks.
+#[derive(Clone)]
+pub struct SharedEngine {
+    engine: Arc<Mutex<ExecutionEngine>>,
+    snapshots: Arc<RwLock<Arc<HashMap<String, MarketSnapshot>>>>,
+}
+
+impl SharedEngine {
+    pub fn new(engine: ExecutionEngine) -> Self {
+        let snapshots = Arc::new(RwLock::new(Arc::new(collect_snapshots(&engine))));
+        SharedEngine {
+            engine: Arc::new(Mutex::new(engine)),
+            snapshots,
+        }
+    }
+
+    /// Advance one step and publish the resulting snapshots
+    pub fn step(&self) -> HashMap<String, StepResult> {
+        self.with_engine(|engine| engine.step())
+    }
+
+    /// Run `f` with exclusive access to the engine (settling, tuning
+    /// configs, ...), then publish its snapshots
+    pub fn with_engine<R>(&self, f: impl FnOnce(&mut ExecutionEngine) -> R) -> R {
+        let mut engine = self.engine.lock().expect("engine mutex poisoned");
+        let result = f(&mut engine);
+        let published = Arc::new(collect_snapshots(&engine));
+        *self.snapshots.write().expect("snapshot lock poisoned") = published;
+        result
+    }
+
+    /// Every market's snapshot as of the last published step
+    pub fn snapshots(&self) -> Arc<HashMap<String, MarketSnapshot>> {
+        Arc::clone(&self.snapshots.read().expect("snapshot lock poisoned"))
+    }
+
+    pub fn snapshot(&self, market_name: &str) -> Option<MarketSnapshot> {
+        self.snapshots().get(market_name).cloned()
+    }
+}
+
+fn collect_snapshots(engine: &ExecutionEngine) -> HashMap<String, MarketSnapshot> {
+    engine
+        .markets
+        .keys()
+        .filter_map(|name| Some((name.clone(), engine.snapshot(name)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_state::MarketState;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn readers_see_whole_monotone_snapshots_while_another_thread_steps() {
+        let markets = ["alpha", "beta", "gamma"]
+            .iter()
+            .map(|name| (name.to_string(), MarketState::new(name, 0.5)))
+            .collect();
+        let shared = SharedEngine::new(ExecutionEngine::new(markets, 260).unwrap());
+        let stepping = AtomicBool::new(true);
+
+        thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    let mut seen: HashMap<String, u64> = HashMap::new();
+                    let mut reads = 0;
+                    while stepping.load(Ordering::Acquire) || reads == 0 {
+                        let snapshots = shared.snapshots();
+                        assert_eq!(snapshots.len(), 3);
+                        for (name, snapshot) in snapshots.iter() {
+                            let last = seen.entry(name.clone()).or_default();
+                            assert!(snapshot.fill_count >= *last, "{} went back in time", name);
+                            *last = snapshot.fill_count;
+                        }
+                        reads += 1;
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for _ in 0..200 {
+                    shared.step();
+                }
+                stepping.store(false, Ordering::Release);
+            });
+        });
+
+        let fills = shared.with_engine(|engine| engine.markets["alpha"].fill_count);
+        assert_eq!(shared.snapshot("alpha").unwrap().fill_count, fills);
+        assert_eq!(shared.with_engine(|engine| engine.time), 200);
+    }
+}
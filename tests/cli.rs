@@ -0,0 +1,103 @@
+//! End-to-end runs of the demo binary, checking what each verbosity level
+//! prints and that every level still writes the output files
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn run(name: &str, args: &[&str]) -> (String, PathBuf) {
+    let out_dir = std::env::temp_dir().join(format!("cli_{}_{}", name, std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_quant_bot_rust"))
+        .args(["--steps", "30", "--out-dir"])
+        .arg(&out_dir)
+        .args(args)
+        .output()
+        .expect("run the demo binary");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    (String::from_utf8(output.stdout).unwrap(), out_dir)
+}
+
+fn assert_files_written(out_dir: &PathBuf) {
+    for file in ["simulation_report.csv", "fills.csv", "summary.json", "trace.json"] {
+        assert!(out_dir.join(file).exists(), "{} missing", file);
+    }
+    std::fs::remove_dir_all(out_dir).unwrap();
+}
+
+#[test]
+fn quiet_prints_only_the_summary() {
+    let (stdout, out_dir) = run("quiet", &["--quiet", "--mc-runs", "2", "--mc-random-inventory", "0.5"]);
+    assert!(stdout.starts_with("📊 Summary Statistics:"), "{}", stdout);
+    assert!(stdout.lines().all(|line| !line.starts_with('✅') && !line.contains("step ")));
+    assert!(!stdout.contains("starting inventory"));
+    assert_files_written(&out_dir);
+}
+
+#[test]
+fn normal_prints_markets_and_summary_without_progress() {
+    let (stdout, out_dir) = run("normal", &[]);
+    assert!(stdout.starts_with("🚀 Quant Execution Bot"));
+    assert!(stdout.contains("📈 Final Market States:"));
+    assert!(stdout.contains("📊 Summary Statistics:"));
+    assert!(!stdout.contains("  step "));
+    assert_files_written(&out_dir);
+
+    // Market blocks come out in name order, run after run
+    let markets: Vec<&str> = stdout.lines().filter_map(|line| line.strip_prefix("🏪 ")).collect();
+    let mut sorted = markets.clone();
+    sorted.sort();
+    assert!(markets.len() > 1);
+    assert_eq!(markets, sorted);
+}
+
+#[test]
+fn verbose_adds_one_progress_line_per_step() {
+    let (stdout, out_dir) = run("verbose", &["--verbose"]);
+    assert_eq!(stdout.lines().filter(|line| line.starts_with("  step ")).count(), 30);
+    assert!(stdout.contains("📈 Final Market States:"));
+    assert_files_written(&out_dir);
+}
+
+#[test]
+fn monte_carlo_seeds_wrap_at_the_top_of_the_range() {
+    let (stdout, out_dir) = run("mc_seed_wrap", &["--quiet", "--seed", "18446744073709551615", "--mc-runs", "2"]);
+    assert!(stdout.starts_with("📊 Summary Statistics:"));
+    assert!(out_dir.join("equity_bands.csv").exists());
+    assert_files_written(&out_dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_flushes_every_streamed_step() {
+    use std::process::Stdio;
+    use std::time::Duration;
+
+    let out_dir = std::env::temp_dir().join(format!("cli_interrupt_{}", std::process::id()));
+    let child = Command::new(env!("CARGO_BIN_EXE_quant_bot_rust"))
+        .args(["--quiet", "--steps", "100000000", "--trace-batch", "100000", "--out-dir"])
+        .arg(&out_dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run the demo binary");
+
+    // Interrupt mid-batch, once some steps have run
+    std::thread::sleep(Duration::from_millis(1500));
+    let sent = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert!(sent.success());
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+
+    let steps: usize = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("⏹️  Interrupted after "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|steps| steps.parse().ok())
+        .unwrap_or_else(|| panic!("no interrupt report in {}", stderr));
+    let trace = std::fs::read_to_string(out_dir.join("trace.ndjson")).unwrap();
+    assert!(steps > 0 && steps < 100_000_000);
+    assert_eq!(trace.lines().count(), steps);
+    assert!(trace.lines().all(|line| serde_json::from_str::<serde_json::Value>(line).is_ok()));
+    assert!(out_dir.join("summary.json").exists());
+    std::fs::remove_dir_all(out_dir).unwrap();
+}